@@ -0,0 +1,360 @@
+// Construction of the github api client, everything all requests have in common lives here.
+//
+// Next to the primary rate limit, the quota octorust reports as `RateLimited`, github has
+// secondary rate limits against too many concurrent or too fast requests. Hitting one is answered
+// with 403 or 429 and pauses all requests of the run for the time github asks for, or for a
+// doubling wait without a hint. Only then the request is retried. With `--limit-rate` requests
+// are throttled up front, so bursts do not run into those limits in the first place.
+
+use async_trait::async_trait;
+use octorust::{
+    Client,
+    ClientError,
+};
+use reqwest::header::{
+    HeaderMap,
+    HeaderValue,
+};
+use reqwest::{
+    Request,
+    Response,
+    StatusCode,
+    Url,
+};
+use reqwest_middleware::{
+    Middleware,
+    Next,
+};
+use reqwest_retry::{
+    policies::ExponentialBackoff,
+    RetryPolicy,
+    RetryTransientMiddleware,
+};
+use retry_policies::RetryDecision;
+use std::sync::{
+    atomic::{
+        AtomicU32,
+        Ordering,
+    },
+    Arc,
+    Mutex,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+use task_local_extensions::Extensions;
+
+/// Retries of a request which hit a secondary rate limit.
+const SECONDARY_RETRIES: u32 = 3;
+
+/// First wait after a secondary rate limit without `retry-after`, doubled with every retry.
+const SECONDARY_WAIT: Duration = Duration::from_secs(60);
+
+/// Version of the REST api rgf is tested with, see
+/// <https://docs.github.com/rest/about-the-rest-api/api-versions>.
+pub const DEFAULT_API_VERSION: &str = "2022-11-28";
+
+/// Options of the client taken from the command line.
+pub struct ClientOptions {
+    pub token: Option<String>,
+    pub api_version: String,
+    pub retry_budget: RetryBudget,
+    /// Follow the redirects github answers with for renamed and transferred repositories.
+    pub follow_redirects: bool,
+    /// Accept any TLS certificate, see `--no-verify-ssl`.
+    pub accept_invalid_certs: bool,
+    /// Print every request with the remaining quota to stderr, see `--verbose`.
+    pub log_rate_limit: bool,
+    /// Requests per minute, see `--limit-rate`.
+    pub limit_rate: Option<u32>,
+}
+
+/// Retries all requests of a run may use together. Clones share the same budget.
+#[derive(Clone, Debug, Default)]
+pub struct RetryBudget(Option<Arc<AtomicU32>>);
+
+impl RetryBudget {
+    /// Without a number of retries the budget is unlimited.
+    pub fn new(retries: Option<u32>) -> RetryBudget {
+        RetryBudget(retries.map(|retries| Arc::new(AtomicU32::new(retries))))
+    }
+
+    /// Retries left, `None` if unlimited.
+    pub fn remaining(&self) -> Option<u32> {
+        self.0.as_ref().map(|left| left.load(Ordering::SeqCst))
+    }
+
+    /// Takes one retry from the budget, false if it is used up.
+    fn take(&self) -> bool {
+        match &self.0 {
+            Some(left) => left.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok(),
+            None => true,
+        }
+    }
+}
+
+/// Retries like `inner`, as long as the budget lasts.
+struct BudgetPolicy {
+    inner: ExponentialBackoff,
+    budget: RetryBudget,
+}
+
+impl RetryPolicy for BudgetPolicy {
+    fn should_retry(&self, n_past_retries: u32) -> RetryDecision {
+        match self.inner.should_retry(n_past_retries) {
+            RetryDecision::Retry { .. } if !self.budget.take() => RetryDecision::DoNotRetry,
+            decision => decision,
+        }
+    }
+}
+
+/// Waits out secondary rate limits, see the top of this file.
+#[derive(Default)]
+struct SecondaryRateLimit {
+    /// All requests wait until then, concurrent ones as well
+    paused_until: Mutex<Option<Instant>>,
+}
+
+impl SecondaryRateLimit {
+    async fn wait(&self) {
+        let until = *self.paused_until.lock().unwrap();
+        if let Some(until) = until {
+            tokio::time::sleep_until(until.into()).await;
+        }
+    }
+
+    fn pause(&self, wait: Duration) {
+        let until = Instant::now() + wait;
+        let mut paused_until = self.paused_until.lock().unwrap();
+        if paused_until.is_none_or(|current| current < until) {
+            *paused_until = Some(until);
+        }
+    }
+}
+
+/// How long to wait if `response` is a secondary rate limit, the `attempt`th one of a request.
+///
+/// The response is given back, its body may have been read to tell.
+async fn secondary_limit(response: Response, attempt: u32) -> reqwest_middleware::Result<(Response, Option<Duration>)> {
+    let status = response.status();
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return Ok((response, None));
+    }
+
+    let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    if let Some(seconds) = header("retry-after").and_then(|v| v.parse().ok()) {
+        return Ok((response, Some(Duration::from_secs(seconds))));
+    }
+    // The used up quota is the primary rate limit, octorust reports that one itself
+    if header("x-ratelimit-remaining").as_deref() == Some("0") {
+        return Ok((response, None));
+    }
+
+    // Without a header only the message tells it apart from other errors
+    let mut builder = http::Response::builder().status(status).version(response.version());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+    let body = response.bytes().await?;
+    let secondary = String::from_utf8_lossy(&body).contains("secondary rate limit");
+    let response = Response::from(builder.body(body).expect("Response parts are valid"));
+    Ok((response, secondary.then(|| SECONDARY_WAIT * 2u32.pow(attempt))))
+}
+
+#[async_trait]
+impl Middleware for SecondaryRateLimit {
+    async fn handle(&self, req: Request, extensions: &mut Extensions, next: Next<'_>) -> reqwest_middleware::Result<Response> {
+        let mut req = req;
+        let mut attempt = 0;
+        loop {
+            self.wait().await;
+            // Requests with a streamed body can not be cloned, they are sent only once
+            let retry = req.try_clone();
+            let response = next.clone().run(req, extensions).await?;
+            let (response, wait) = secondary_limit(response, attempt).await?;
+            match (wait, retry) {
+                (Some(wait), Some(retry)) if attempt < SECONDARY_RETRIES => {
+                    eprintln!("Warning: Hit a secondary rate limit of github, pausing all requests for {}s", wait.as_secs());
+                    self.pause(wait);
+                    req = retry;
+                    attempt += 1;
+                },
+                _ => return Ok(response),
+            }
+        }
+    }
+}
+
+/// Token bucket holding a single token, which refills every `interval`.
+///
+/// Every request takes the next free slot and waits for it, so concurrent requests are spaced
+/// by `interval` as well. A slot left unused is not saved up for a later burst.
+struct Throttle {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl Throttle {
+    fn new(per_minute: u32) -> Throttle {
+        Throttle {
+            interval: Duration::from_secs(60) / per_minute,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for Throttle {
+    async fn handle(&self, req: Request, extensions: &mut Extensions, next: Next<'_>) -> reqwest_middleware::Result<Response> {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+        next.run(req, extensions).await
+    }
+}
+
+/// Prints every request with its url and the remaining quota github reports with the response.
+struct RateLimitLog;
+
+#[async_trait]
+impl Middleware for RateLimitLog {
+    async fn handle(&self, req: Request, extensions: &mut Extensions, next: Next<'_>) -> reqwest_middleware::Result<Response> {
+        let method = req.method().clone();
+        let url = redacted_url(req.url());
+        let response = next.run(req, extensions).await?;
+        let remaining = response.headers().get("x-ratelimit-remaining").and_then(|v| v.to_str().ok()).unwrap_or("-");
+        eprintln!("{} {} {} remaining:{}", method, url, response.status().as_u16(), remaining);
+        Ok(response)
+    }
+}
+
+/// Query parameters whose values are secrets.
+const SECRET_PARAMS: &[&str] = &["access_token", "client_secret", "token", "key", "password"];
+
+/// `url` with its credentials and the values of secret query parameters replaced by `***`.
+///
+/// The token is sent in a header, this only guards against one ending up in the url anyway.
+pub fn redacted_url(url: &Url) -> String {
+    let mut url = url.clone();
+    if !url.username().is_empty() {
+        let _ = url.set_username("***");
+    }
+    if url.password().is_some() {
+        let _ = url.set_password(Some("***"));
+    }
+    if url.query().is_some() {
+        let pairs: Vec<(String, String)> = url.query_pairs()
+            .map(|(name, value)| {
+                let secret = SECRET_PARAMS.iter().any(|secret| name.eq_ignore_ascii_case(secret));
+                (name.into_owned(), if secret { "***".to_string() } else { value.into_owned() })
+            })
+            .collect();
+        if pairs.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+    }
+    url.to_string()
+}
+
+/// Prefix of fine-grained personal access tokens.
+const FINE_GRAINED_PREFIX: &str = "github_pat_";
+
+/// What to check if `e` is likely caused by the repository access of the token.
+///
+/// A fine-grained token only sees the repositories selected for it. github answers for any other
+/// with 404 as if it did not exist, and with 403 if the token lacks a permission.
+pub fn token_hint(e: &ClientError, token: Option<&str>) -> Option<&'static str> {
+    let ClientError::HttpError { status, error, .. } = e else { return None };
+    let fine_grained = token.is_some_and(|token| token.starts_with(FINE_GRAINED_PREFIX));
+    if *status == StatusCode::FORBIDDEN && error.contains("Resource not accessible by personal access token") {
+        return Some("the token lacks a permission, a fine-grained token needs read access to the metadata of the repository, see https://github.com/settings/personal-access-tokens");
+    }
+    if *status == StatusCode::NOT_FOUND && fine_grained {
+        return Some("fine-grained tokens only see the repositories selected for them, check the repository access of the token at https://github.com/settings/personal-access-tokens");
+    }
+    None
+}
+
+/// Builds the api client like `Client::new` does, but pins the api version on every request.
+pub fn client(options: &ClientOptions) -> Result<Client, String> {
+    let mut headers = HeaderMap::new();
+    let version = HeaderValue::from_str(&options.api_version)
+        .map_err(|_| format!("Invalid api version '{}'", options.api_version))?;
+    headers.insert("X-GitHub-Api-Version", version);
+
+    let redirect = if options.follow_redirects {
+        reqwest::redirect::Policy::default()
+    } else {
+        reqwest::redirect::Policy::none()
+    };
+    let http = reqwest::Client::builder()
+        .redirect(redirect)
+        .danger_accept_invalid_certs(options.accept_invalid_certs)
+        .default_headers(headers)
+        .build()
+        .map_err(|e| format!("Failed to create gh client: {}", e))?;
+
+    // Same retries of transient errors as octorust uses itself, limited by the budget of the run
+    let retry_policy = BudgetPolicy {
+        inner: ExponentialBackoff::builder().build_with_max_retries(3),
+        budget: options.retry_budget.clone(),
+    };
+    let mut http = reqwest_middleware::ClientBuilder::new(http)
+        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+        // Inside the retries, so a 429 is waited out here and not retried right away
+        .with(SecondaryRateLimit::default());
+    if let Some(per_minute) = options.limit_rate {
+        // Inside the retries and pauses, so every attempt takes a slot
+        http = http.with(Throttle::new(per_minute));
+    }
+    if options.log_rate_limit {
+        // Innermost, so every retry shows up as well
+        http = http.with(RateLimitLog);
+    }
+    let http = http.build();
+
+    let credentials = options.token.clone().map(octorust::auth::Credentials::Token);
+    Ok(Client::custom("myAgent", credentials, http))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_error(status: StatusCode, body: &str) -> ClientError {
+        ClientError::HttpError { status, headers: HeaderMap::new(), error: body.to_string() }
+    }
+
+    const BODY_403: &str = r#"{"message":"Resource not accessible by personal access token","documentation_url":"https://docs.github.com/rest"}"#;
+    const BODY_404: &str = r#"{"message":"Not Found","documentation_url":"https://docs.github.com/rest"}"#;
+
+    #[test]
+    fn token_hint_of_a_missing_permission() {
+        let hint = token_hint(&http_error(StatusCode::FORBIDDEN, BODY_403), Some("github_pat_11ABC"));
+        assert_eq!(hint, Some("the token lacks a permission, a fine-grained token needs read access to the metadata of the repository, see https://github.com/settings/personal-access-tokens"));
+        // The body tells, not the kind of token
+        assert_eq!(token_hint(&http_error(StatusCode::FORBIDDEN, BODY_403), Some("ghp_abc")), hint);
+    }
+
+    #[test]
+    fn token_hint_of_a_repository_not_selected_for_a_fine_grained_token() {
+        let hint = token_hint(&http_error(StatusCode::NOT_FOUND, BODY_404), Some("github_pat_11ABC"));
+        assert_eq!(hint, Some("fine-grained tokens only see the repositories selected for them, check the repository access of the token at https://github.com/settings/personal-access-tokens"));
+    }
+
+    #[test]
+    fn token_hint_without_a_likely_token_cause() {
+        // Without a fine-grained token a 404 has other causes
+        assert_eq!(token_hint(&http_error(StatusCode::NOT_FOUND, BODY_404), Some("ghp_abc")), None);
+        assert_eq!(token_hint(&http_error(StatusCode::NOT_FOUND, BODY_404), None), None);
+        assert_eq!(token_hint(&http_error(StatusCode::FORBIDDEN, r#"{"message":"API rate limit exceeded"}"#), Some("github_pat_11ABC")), None);
+        assert_eq!(token_hint(&ClientError::RateLimited { duration: 60 }, Some("github_pat_11ABC")), None);
+    }
+}
@@ -0,0 +1,36 @@
+// Job summary of GitHub Actions.
+//
+// Inside a workflow `GITHUB_STEP_SUMMARY` names a file, markdown appended to it is shown on the
+// summary page of the job. Outside of GitHub Actions nothing is written.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+/// Appends `markdown` to the job summary, if rgf runs in GitHub Actions and `enabled`.
+pub fn append(markdown: &str, enabled: bool) {
+    let Some(path) = env::var_os("GITHUB_STEP_SUMMARY").filter(|path| !path.is_empty()) else { return };
+    if !enabled {
+        return;
+    }
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(format!("{}\n", markdown).as_bytes()));
+    if let Err(e) = written {
+        eprintln!("Warning: Failed to write the job summary: {}", e);
+    }
+}
+
+/// Markdown table with `header` and `rows`.
+pub fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    // A `|` inside a cell would end it
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.iter().map(|cell| cell.replace('|', "\\|")).collect::<Vec<_>>().join(" | "));
+    let mut out = line(header.iter().map(|cell| cell.to_string()).collect());
+    out.push_str(&line(header.iter().map(|_| "---".to_string()).collect()));
+    for row in rows {
+        out.push_str(&line(row.clone()));
+    }
+    out
+}
@@ -0,0 +1,79 @@
+// Shell completions of `--completions` and `--completions-install`.
+//
+// The scripts are generated from the command line definition, so they always know every option
+// of the binary they come from.
+
+use clap::Command;
+use clap_complete::{
+    Generator,
+    Shell,
+};
+use directories::BaseDirs;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+/// Writes the completions of `cmd`, invoked as `name`, for `shell` to `out`.
+pub fn generate(shell: Shell, cmd: &mut Command, name: &str, out: &mut dyn Write) {
+    clap_complete::generate(shell, cmd, name, out);
+}
+
+fn home_dir() -> Result<PathBuf, String> {
+    match BaseDirs::new() {
+        Some(dirs) => Ok(dirs.home_dir().to_path_buf()),
+        None => Err("No home directory found, please pass --completions-install-dir".to_string()),
+    }
+}
+
+/// `$<var>` if set to an absolute path, else `<home>/<fallback>`, like the XDG base directories.
+fn xdg_dir(var: &str, fallback: &str) -> Result<PathBuf, String> {
+    match env::var_os(var).map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+        Some(dir) => Ok(dir),
+        None => Ok(home_dir()?.join(fallback)),
+    }
+}
+
+/// Directory `shell` loads completions from without further configuration, as far as it has one.
+pub fn default_dir(shell: Shell) -> Result<PathBuf, String> {
+    match shell {
+        Shell::Bash => Ok(xdg_dir("XDG_DATA_HOME", ".local/share")?.join("bash-completion/completions")),
+        Shell::Fish => Ok(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("fish/completions")),
+        // Not loaded on its own, see `hint`
+        Shell::Zsh => Ok(home_dir()?.join(".zfunc")),
+        _ => Err(format!("{} has no conventional completions directory, please pass --completions-install-dir", shell)),
+    }
+}
+
+/// What else is needed for the shell to find the completions in `default_dir`.
+pub fn hint(shell: Shell) -> Option<&'static str> {
+    match shell {
+        Shell::Zsh => Some("Add `fpath+=~/.zfunc` before `compinit` to ~/.zshrc, if it is not there yet"),
+        _ => None,
+    }
+}
+
+/// Writes the completions of `cmd` for `shell` into `dir`, or else `default_dir`, and returns the
+/// path of the written file. Missing directories are created.
+pub fn install(shell: Shell, cmd: &mut Command, name: &str, dir: Option<&Path>) -> Result<PathBuf, String> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => default_dir(shell)?,
+    };
+    if let Err(e) = fs::create_dir_all(&dir) {
+        return Err(format!("Failed to create {}: {}", dir.display(), e));
+    }
+
+    // bash-completion looks up the completions of a command by its plain name
+    let file_name = if shell == Shell::Bash { name.to_string() } else { shell.file_name(name) };
+    let path = dir.join(file_name);
+    let mut script = Vec::new();
+    generate(shell, cmd, name, &mut script);
+    match fs::write(&path, script) {
+        Ok(_) => Ok(path),
+        Err(e) => Err(format!("{} is not writable: {}", path.display(), e)),
+    }
+}
@@ -0,0 +1,462 @@
+// A small expression language to filter forks, see `--filter-expr`.
+//
+//     expr       := and ("||" and)*
+//     and        := unary ("&&" unary)*
+//     unary      := "!" unary | "(" expr ")" | field [op value]
+//     op         := "==" | "!=" | "<" | "<=" | ">" | ">="
+//     value      := number | "string" | true | false
+//
+// Fields and values are type checked while parsing, so a bad expression fails before any api
+// call is made.
+
+use chrono::{
+    DateTime,
+    NaiveDate,
+    Utc,
+};
+use octorust::types::MinimalRepository;
+use std::fmt;
+
+/// Fields of a fork usable in an expression.
+const FIELDS: &[(&str, Kind)] = &[
+    ("stars", Kind::Number),
+    ("forks", Kind::Number),
+    ("watchers", Kind::Number),
+    ("open_issues", Kind::Number),
+    ("size", Kind::Number),
+    ("name", Kind::Text),
+    ("owner", Kind::Text),
+    ("language", Kind::Text),
+    ("license", Kind::Text),
+    ("description", Kind::Text),
+    ("default_branch", Kind::Text),
+    ("pushed_at", Kind::Date),
+    ("created_at", Kind::Date),
+    ("updated_at", Kind::Date),
+    ("archived", Kind::Bool),
+    ("disabled", Kind::Bool),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Number,
+    Text,
+    Date,
+    Bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Date(DateTime<Utc>),
+    Bool(bool),
+}
+
+/// A parsed expression, see `parse`.
+#[derive(Clone, Debug)]
+pub struct Expr(Node);
+
+#[derive(Clone, Debug)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Compare(&'static str, Op, Value),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Text(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "`{}`", ident),
+            Token::Number(number) => write!(f, "`{}`", number),
+            Token::Text(text) => write!(f, "`\"{}\"`", text),
+            Token::Op(op) => write!(f, "`{}`", match op {
+                Op::Eq => "==",
+                Op::Ne => "!=",
+                Op::Lt => "<",
+                Op::Le => "<=",
+                Op::Gt => ">",
+                Op::Ge => ">=",
+            }),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Not => write!(f, "`!`"),
+            Token::Open => write!(f, "`(`"),
+            Token::Close => write!(f, "`)`"),
+            Token::End => write!(f, "end of expression"),
+        }
+    }
+}
+
+/// Parses `input` into an expression, usable as clap value parser.
+///
+/// The error names the offending token and points at its column.
+pub fn parse(input: &str) -> Result<Expr, String> {
+    let error = |column: usize, message: String| {
+        format!("{} at column {}\n    {}\n    {}^", message, column + 1, input, " ".repeat(column))
+    };
+    let tokens = tokenize(input).map_err(|(column, message)| error(column, message))?;
+    let mut parser = Parser { tokens, next: 0 };
+    let expr = parser.or().map_err(|(column, message)| error(column, message))?;
+    match parser.peek() {
+        (_, Token::End) => Ok(Expr(expr)),
+        (column, token) => Err(error(*column, format!("unexpected {}", token))),
+    }
+}
+
+impl Expr {
+    /// Whether `fork` matches. Comparisons with a missing value, e.g. a fork never pushed to, do not match.
+    pub fn matches(&self, fork: &MinimalRepository) -> bool {
+        self.0.matches(fork)
+    }
+}
+
+impl Node {
+    fn matches(&self, fork: &MinimalRepository) -> bool {
+        match self {
+            Node::And(left, right) => left.matches(fork) && right.matches(fork),
+            Node::Or(left, right) => left.matches(fork) || right.matches(fork),
+            Node::Not(expr) => !expr.matches(fork),
+            Node::Compare(field, op, value) => match (field_value(fork, field), value) {
+                (Some(Value::Number(a)), Value::Number(b)) => compare(*op, a.partial_cmp(b)),
+                // Names on github do not depend on case
+                (Some(Value::Text(a)), Value::Text(b)) => compare(*op, Some(a.to_lowercase().cmp(&b.to_lowercase()))),
+                (Some(Value::Date(a)), Value::Date(b)) => compare(*op, Some(a.cmp(b))),
+                (Some(Value::Bool(a)), Value::Bool(b)) => compare(*op, Some(a.cmp(b))),
+                _ => false,
+            },
+        }
+    }
+}
+
+fn compare(op: Op, ordering: Option<std::cmp::Ordering>) -> bool {
+    let Some(ordering) = ordering else { return false };
+    match op {
+        Op::Eq => ordering.is_eq(),
+        Op::Ne => ordering.is_ne(),
+        Op::Lt => ordering.is_lt(),
+        Op::Le => ordering.is_le(),
+        Op::Gt => ordering.is_gt(),
+        Op::Ge => ordering.is_ge(),
+    }
+}
+
+fn field_value(fork: &MinimalRepository, field: &str) -> Option<Value> {
+    let text = |text: &str| if text.is_empty() { None } else { Some(Value::Text(text.to_string())) };
+    match field {
+        "stars" => Some(Value::Number(fork.stargazers_count as f64)),
+        "forks" => Some(Value::Number(fork.forks_count as f64)),
+        "watchers" => Some(Value::Number(fork.watchers_count as f64)),
+        "open_issues" => Some(Value::Number(fork.open_issues_count as f64)),
+        "size" => Some(Value::Number(fork.size as f64)),
+        "name" => text(&fork.full_name),
+        "owner" => text(fork.full_name.split('/').next().unwrap_or_default()),
+        "language" => text(&fork.language),
+        "license" => fork.license.as_ref().and_then(|license| text(&license.spdx_id)),
+        "description" => text(&fork.description),
+        "default_branch" => text(&fork.default_branch),
+        "pushed_at" => fork.pushed_at.map(Value::Date),
+        "created_at" => fork.created_at.map(Value::Date),
+        "updated_at" => fork.updated_at.map(Value::Date),
+        "archived" => Some(Value::Bool(fork.archived)),
+        "disabled" => Some(Value::Bool(fork.disabled)),
+        _ => None,
+    }
+}
+
+/// Splits `input` into tokens with their column, the last one is always `Token::End`.
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, (usize, String)> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let two: String = chars[i..chars.len().min(i + 2)].iter().collect();
+        let token = match (c, two.as_str()) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            },
+            (_, "&&") => Token::And,
+            (_, "||") => Token::Or,
+            (_, "==") => Token::Op(Op::Eq),
+            (_, "!=") => Token::Op(Op::Ne),
+            (_, "<=") => Token::Op(Op::Le),
+            (_, ">=") => Token::Op(Op::Ge),
+            ('<', _) => Token::Op(Op::Lt),
+            ('>', _) => Token::Op(Op::Gt),
+            ('!', _) => Token::Not,
+            ('(', _) => Token::Open,
+            (')', _) => Token::Close,
+            ('"', _) => {
+                let Some(len) = chars[i + 1..].iter().position(|c| *c == '"') else {
+                    return Err((start, "unterminated string".to_string()));
+                };
+                i += len + 2;
+                tokens.push((start, Token::Text(chars[start + 1..i - 1].iter().collect())));
+                continue;
+            },
+            (c, _) if c.is_ascii_digit() => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                match number.parse() {
+                    Ok(number) => tokens.push((start, Token::Number(number))),
+                    Err(_) => return Err((start, format!("invalid number `{}`", number))),
+                }
+                continue;
+            },
+            (c, _) if c.is_ascii_alphabetic() || c == '_' => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push((start, Token::Ident(chars[start..i].iter().collect())));
+                continue;
+            },
+            (c, _) => return Err((start, format!("unexpected character `{}`", c))),
+        };
+        i += match token {
+            Token::And | Token::Or | Token::Op(Op::Eq | Op::Ne | Op::Le | Op::Ge) => 2,
+            _ => 1,
+        };
+        tokens.push((start, token));
+    }
+    tokens.push((chars.len(), Token::End));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &(usize, Token) {
+        &self.tokens[self.next]
+    }
+
+    fn take(&mut self) -> (usize, Token) {
+        let token = self.tokens[self.next].clone();
+        // Stay at `Token::End`
+        self.next = (self.next + 1).min(self.tokens.len() - 1);
+        token
+    }
+
+    fn or(&mut self) -> Result<Node, (usize, String)> {
+        let mut expr = self.and()?;
+        while self.peek().1 == Token::Or {
+            self.take();
+            expr = Node::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Node, (usize, String)> {
+        let mut expr = self.unary()?;
+        while self.peek().1 == Token::And {
+            self.take();
+            expr = Node::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Node, (usize, String)> {
+        match self.take() {
+            (_, Token::Not) => Ok(Node::Not(Box::new(self.unary()?))),
+            (_, Token::Open) => {
+                let expr = self.or()?;
+                match self.take() {
+                    (_, Token::Close) => Ok(expr),
+                    (column, token) => Err((column, format!("expected `)`, found {}", token))),
+                }
+            },
+            (column, Token::Ident(ident)) => self.comparison(column, &ident),
+            (column, token) => Err((column, format!("expected a field, found {}", token))),
+        }
+    }
+
+    fn comparison(&mut self, column: usize, ident: &str) -> Result<Node, (usize, String)> {
+        let Some((field, kind)) = FIELDS.iter().find(|(field, _)| *field == ident) else {
+            let fields: Vec<&str> = FIELDS.iter().map(|(field, _)| *field).collect();
+            return Err((column, format!("unknown field `{}`, known are {}", ident, fields.join(", "))));
+        };
+
+        let op = match self.peek() {
+            (_, Token::Op(op)) => *op,
+            // A bare boolean field, e.g. `!archived`
+            _ if *kind == Kind::Bool => return Ok(Node::Compare(field, Op::Eq, Value::Bool(true))),
+            (column, token) => return Err((*column, format!("expected a comparison after `{}`, found {}", field, token))),
+        };
+        let (op_column, _) = self.take();
+
+        let (column, token) = self.take();
+        let value = match (kind, token) {
+            (Kind::Number, Token::Number(number)) => Value::Number(number),
+            (Kind::Text, Token::Text(text)) => Value::Text(text),
+            (Kind::Date, Token::Text(text)) => match parse_date(&text) {
+                Some(date) => Value::Date(date),
+                None => return Err((column, format!("invalid date `\"{}\"`, expected e.g. \"2024-01-31\"", text))),
+            },
+            (Kind::Bool, Token::Ident(ident)) if ident == "true" || ident == "false" => Value::Bool(ident == "true"),
+            (kind, token) => {
+                let expected = match kind {
+                    Kind::Number => "a number",
+                    Kind::Text => "a string",
+                    Kind::Date => "a date string",
+                    Kind::Bool => "true or false",
+                };
+                return Err((column, format!("expected {} for `{}`, found {}", expected, field, token)));
+            },
+        };
+
+        if matches!(value, Value::Text(_) | Value::Bool(_)) && !matches!(op, Op::Eq | Op::Ne) {
+            return Err((op_column, format!("`{}` can only be compared with `==` or `!=`", field)));
+        }
+        Ok(Node::Compare(field, op, value))
+    }
+}
+
+/// Parses a date like `2024-01-31` (midnight utc) or a full RFC 3339 timestamp.
+fn parse_date(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Some(date.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fork(value: serde_json::Value) -> MinimalRepository {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn matches(input: &str, fork: &MinimalRepository) -> bool {
+        parse(input).unwrap().matches(fork)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let fork = fork(serde_json::json!({ "full_name": "owner/repo", "stargazers_count": 0, "forks_count": 5 }));
+        assert!(matches("stars > 10 && forks > 1 || forks > 3", &fork));
+        assert!(matches("forks > 3 || stars > 10 && forks > 100", &fork));
+        assert!(!matches("(forks > 3 || stars > 10) && forks > 100", &fork));
+        assert!(!matches("stars > 10 || forks > 3 && forks > 100", &fork));
+    }
+
+    #[test]
+    fn not_negates_the_next_term() {
+        let fork = fork(serde_json::json!({ "full_name": "owner/repo", "stargazers_count": 0, "archived": true }));
+        assert!(matches("archived", &fork));
+        assert!(!matches("!archived", &fork));
+        assert!(matches("!!archived", &fork));
+        assert!(!matches("!archived && stars == 0", &fork));
+        assert!(matches("!(archived && stars > 1)", &fork));
+        assert!(matches("archived == true && disabled != true", &fork));
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        let fork = fork(serde_json::json!({ "full_name": "owner/repo", "stargazers_count": 9 }));
+        // Not `"9" > "10"` as strings
+        assert!(matches("stars < 10", &fork));
+        assert!(matches("stars == 9.0", &fork));
+        assert!(matches("stars >= 9 && stars <= 9", &fork));
+        assert!(!matches("stars != 9", &fork));
+        assert!(!matches("stars > 9", &fork));
+    }
+
+    #[test]
+    fn strings_compare_without_case() {
+        let fork = fork(serde_json::json!({ "full_name": "Owner/Repo", "language": "Rust" }));
+        assert!(matches(r#"language == "rust""#, &fork));
+        assert!(matches(r#"owner == "OWNER" && name == "owner/repo""#, &fork));
+        assert!(matches(r#"language != "Go""#, &fork));
+        // A fork without a description matches neither
+        assert!(!matches(r#"description == """#, &fork));
+        assert!(!matches(r#"description != "x""#, &fork));
+    }
+
+    #[test]
+    fn dates_compare_as_points_in_time() {
+        let fork = fork(serde_json::json!({ "full_name": "owner/repo", "pushed_at": "2024-03-01T12:00:00Z" }));
+        assert!(matches(r#"pushed_at > "2024-02-29""#, &fork));
+        assert!(!matches(r#"pushed_at >= "2024-03-02""#, &fork));
+        assert!(matches(r#"pushed_at < "2024-03-01T13:00:00Z""#, &fork));
+        // 11:00 in utc
+        assert!(!matches(r#"pushed_at < "2024-03-01T13:00:00+02:00""#, &fork));
+        // Never pushed to
+        assert!(!matches(r#"created_at < "2100-01-01""#, &fork));
+        assert!(matches(r#"!(created_at < "2100-01-01")"#, &fork));
+    }
+
+    #[test]
+    fn error_of_an_unknown_field() {
+        assert_eq!(
+            parse("stras > 1").unwrap_err(),
+            "unknown field `stras`, known are stars, forks, watchers, open_issues, size, name, owner, language, license, \
+             description, default_branch, pushed_at, created_at, updated_at, archived, disabled at column 1\n    stras > 1\n    ^"
+        );
+    }
+
+    #[test]
+    fn error_of_a_value_of_the_wrong_kind() {
+        assert_eq!(parse(r#"stars > "10""#).unwrap_err(), "expected a number for `stars`, found `\"10\"` at column 9\n    stars > \"10\"\n            ^");
+        assert_eq!(parse("archived == 1").unwrap_err(), "expected true or false for `archived`, found `1` at column 13\n    archived == 1\n                ^");
+        assert_eq!(parse(r#"name < "a""#).unwrap_err(), "`name` can only be compared with `==` or `!=` at column 6\n    name < \"a\"\n         ^");
+        assert_eq!(
+            parse(r#"pushed_at > "yesterday""#).unwrap_err(),
+            "invalid date `\"yesterday\"`, expected e.g. \"2024-01-31\" at column 13\n    pushed_at > \"yesterday\"\n                ^"
+        );
+    }
+
+    #[test]
+    fn error_of_a_missing_token() {
+        assert_eq!(parse("stars").unwrap_err(), "expected a comparison after `stars`, found end of expression at column 6\n    stars\n         ^");
+        assert_eq!(parse("stars > 1 &&").unwrap_err(), "expected a field, found end of expression at column 13\n    stars > 1 &&\n                ^");
+        assert_eq!(parse("(stars > 1").unwrap_err(), "expected `)`, found end of expression at column 11\n    (stars > 1\n              ^");
+        assert_eq!(parse("stars > 1 forks > 1").unwrap_err(), "unexpected `forks` at column 11\n    stars > 1 forks > 1\n              ^");
+    }
+
+    #[test]
+    fn error_of_a_bad_token() {
+        assert_eq!(parse("stars > 1 & forks > 1").unwrap_err(), "unexpected character `&` at column 11\n    stars > 1 & forks > 1\n              ^");
+        assert_eq!(parse("stars > 1.2.3").unwrap_err(), "invalid number `1.2.3` at column 9\n    stars > 1.2.3\n            ^");
+        assert_eq!(parse(r#"name == "a"#).unwrap_err(), "unterminated string at column 9\n    name == \"a\n            ^");
+    }
+
+    #[test]
+    fn error_columns_count_characters() {
+        assert_eq!(parse(r#"name == "ä" || ("#).unwrap_err(), "expected a field, found end of expression at column 17\n    name == \"ä\" || (\n                    ^");
+    }
+}
@@ -0,0 +1,263 @@
+// Writes many remotes to the git config at once.
+//
+// libgit2 rewrites and re-reads the whole config file for every single entry, so adding
+// thousands of remotes with `Repository::remote` and `remote_set_pushurl` gets slower with every
+// remote. Here they are appended in one write.
+
+use clap::ValueEnum;
+use git2::Repository;
+use std::fs::{
+    self,
+    OpenOptions,
+};
+use std::io::{
+    self,
+    Write,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+/// Config file the remotes are written to.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    /// The config of the repository, shared by all its worktrees
+    Local,
+    /// The `config.worktree` of the current worktree, needs `extensions.worktreeConfig`
+    Worktree,
+}
+
+pub struct NewRemote {
+    pub name: String,
+    pub url: String,
+    pub fetch: String,
+    pub push_url: Option<String>,
+    /// Written as `remote.<name>.description`, git itself ignores it
+    pub description: Option<String>,
+}
+
+/// Fetch refspec git itself sets up for the remote `name`.
+pub fn default_refspec(name: &str) -> String {
+    format!("+refs/heads/*:refs/remotes/{}/*", name)
+}
+
+/// Git dir holding the config shared by all worktrees.
+///
+/// git2 has no binding for `git_repository_commondir`, so it is read like git does: a linked
+/// worktree has a `commondir` file pointing at it, relative to its own git dir.
+pub fn common_dir(repo: &Repository) -> PathBuf {
+    if repo.is_worktree() {
+        if let Ok(commondir) = fs::read_to_string(repo.path().join("commondir")) {
+            // The path may be absolute, then join replaces the git dir of the worktree
+            let dir = repo.path().join(commondir.trim_end());
+            return dir.canonicalize().unwrap_or(dir);
+        }
+    }
+    repo.path().to_path_buf()
+}
+
+/// Path of the config file of `scope`.
+///
+/// Without `extensions.worktreeConfig` git ignores `config.worktree`, remotes written there would
+/// silently be missing.
+pub fn config_path(repo: &Repository, scope: Scope) -> Result<PathBuf, String> {
+    match scope {
+        Scope::Local => Ok(common_dir(repo).join("config")),
+        Scope::Worktree => {
+            let enabled = repo.config().and_then(|config| config.get_bool("extensions.worktreeConfig")).unwrap_or(false);
+            if !enabled {
+                return Err("Worktree config is not enabled, run `git config extensions.worktreeConfig true` first".to_string());
+            }
+            Ok(repo.path().join("config.worktree"))
+        },
+    }
+}
+
+/// Names of the remotes with a url in the config file at `path`.
+///
+/// libgit2 does not read `config.worktree`, the remotes of the worktree scope are only known from
+/// the file itself. A missing or unreadable file has none.
+pub fn remote_names(path: &Path) -> Vec<String> {
+    let mut names = Vec::new();
+    if !path.exists() {
+        return names;
+    }
+    let Ok(config) = git2::Config::open(path) else { return names };
+    let Ok(mut entries) = config.entries(Some(r"remote\..*\.url")) else { return names };
+    while let Some(Ok(entry)) = entries.next() {
+        let name = entry.name().and_then(|key| key.strip_prefix("remote.")?.strip_suffix(".url"));
+        if let Some(name) = name {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Quotes `value` for the git config file format.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quotes `value` only if it would not be read back unchanged, like git does.
+fn value(value: &str) -> String {
+    let special = |c: char| matches!(c, '"' | '\\' | '#' | ';');
+    if value.contains(special) || value.trim() != value {
+        quote(value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Appends the sections of all `remotes` to the config file at `path` in a single write.
+///
+/// The config is written like git does it: into `<path>.lock` first, which is then renamed over
+/// the config. A concurrently running git therefore either fails on the lock or sees all remotes.
+/// See `config_path` for the file of a scope, by default the remotes are shared by all worktrees.
+pub fn add_remotes(path: &Path, remotes: &[NewRemote]) -> io::Result<()> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock_path = PathBuf::from(lock_path);
+
+    // Taken before reading, an entry git writes meanwhile would be lost otherwise
+    let mut lock = match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+        Ok(lock) => lock,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(io::Error::new(e.kind(), format!("{} exists, is another git process running?", lock_path.display())));
+        },
+        Err(e) => return Err(e),
+    };
+    let written = with_remotes(path, remotes).and_then(|content| lock.write_all(content.as_bytes())).and_then(|_| lock.sync_all());
+    drop(lock);
+    match written.and_then(|_| fs::rename(&lock_path, path)) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&lock_path);
+            Err(e)
+        },
+    }
+}
+
+/// Content of the config file at `path` with the sections of `remotes` appended.
+fn with_remotes(path: &Path, remotes: &[NewRemote]) -> io::Result<String> {
+    // config.worktree does not exist before its first entry
+    let mut content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && path.ends_with("config.worktree") => String::new(),
+        Err(e) => return Err(e),
+    };
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for remote in remotes {
+        content.push_str(&format!("[remote {}]\n", quote(&remote.name)));
+        content.push_str(&format!("\turl = {}\n", value(&remote.url)));
+        content.push_str(&format!("\tfetch = {}\n", value(&remote.fetch)));
+        if let Some(push_url) = &remote.push_url {
+            content.push_str(&format!("\tpushurl = {}\n", value(push_url)));
+        }
+        if let Some(description) = &remote.description {
+            content.push_str(&format!("\tdescription = {}\n", value(description)));
+        }
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=rgf", "-c", "user.email=rgf@example.com", "-C"])
+            .arg(dir)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    /// A repository with a commit and a linked worktree of it.
+    fn repo_with_worktree() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main");
+        let linked = dir.path().join("linked");
+        fs::create_dir(&main).unwrap();
+        git(&main, &["init", "-q"]);
+        git(&main, &["commit", "-q", "--allow-empty", "-m", "init"]);
+        git(&main, &["worktree", "add", "-q", linked.to_str().unwrap()]);
+        (dir, main, linked)
+    }
+
+    fn remote(name: &str) -> NewRemote {
+        NewRemote {
+            name: name.to_string(),
+            url: format!("https://github.com/{}/repo.git", name),
+            fetch: default_refspec(name),
+            push_url: None,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn a_held_lock_keeps_the_config_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let path = repo.path().join("config");
+        let before = fs::read_to_string(&path).unwrap();
+        fs::write(repo.path().join("config.lock"), "").unwrap();
+
+        let e = add_remotes(&path, &[remote("rgf__owner_repo")]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        // The lock of the other process is not ours to remove
+        assert!(repo.path().join("config.lock").exists());
+    }
+
+    #[test]
+    fn remotes_added_in_a_linked_worktree_are_shared() {
+        let (_dir, main, linked) = repo_with_worktree();
+        let repo = Repository::discover(&linked).unwrap();
+        assert!(repo.is_worktree());
+
+        let path = config_path(&repo, Scope::Local).unwrap();
+        assert_eq!(path, main.join(".git/config").canonicalize().unwrap());
+        add_remotes(&path, &[remote("rgf__owner_repo")]).unwrap();
+
+        for dir in [&main, &linked] {
+            let repo = Repository::discover(dir).unwrap();
+            let found = repo.find_remote("rgf__owner_repo").unwrap();
+            assert_eq!(found.url(), Some("https://github.com/rgf__owner_repo/repo.git"));
+        }
+        assert!(!main.join(".git/config.lock").exists());
+    }
+
+    #[test]
+    fn worktree_scope_needs_the_extension() {
+        let (_dir, _main, linked) = repo_with_worktree();
+        let repo = Repository::discover(&linked).unwrap();
+        assert_eq!(config_path(&repo, Scope::Worktree).unwrap_err(), "Worktree config is not enabled, run `git config extensions.worktreeConfig true` first");
+    }
+
+    #[test]
+    fn remotes_of_the_worktree_scope_stay_in_the_worktree() {
+        let (_dir, main, linked) = repo_with_worktree();
+        git(&main, &["config", "extensions.worktreeConfig", "true"]);
+        let repo = Repository::discover(&linked).unwrap();
+
+        let path = config_path(&repo, Scope::Worktree).unwrap();
+        assert_eq!(path.file_name().unwrap(), "config.worktree");
+        add_remotes(&path, &[remote("rgf__owner_repo")]).unwrap();
+
+        // libgit2 does not read config.worktree, git does
+        let remotes = |dir: &Path| {
+            let out = Command::new("git").arg("-C").arg(dir).arg("remote").output().unwrap();
+            String::from_utf8(out.stdout).unwrap()
+        };
+        assert_eq!(remotes(&linked), "rgf__owner_repo\n");
+        assert_eq!(remotes(&main), "");
+        assert_eq!(remote_names(&path), ["rgf__owner_repo"]);
+        assert!(remote_names(&main.join(".git/config")).is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+// Work queue of the remote workers with a limit of operations per host.
+//
+// Nearly all remotes point at github.com, so `--jobs` alone decides how many connections go to
+// the same host. With a limit a worker takes the next item whose host has a free slot and only
+// waits if every remaining item is on a busy host.
+
+use std::collections::HashMap;
+use std::sync::{
+    Condvar,
+    Mutex,
+};
+
+struct State<T> {
+    items: Vec<(String, T)>,
+    /// Operations in flight per host
+    busy: HashMap<String, usize>,
+}
+
+pub struct HostQueue<T> {
+    state: Mutex<State<T>>,
+    freed: Condvar,
+    /// Operations per host at a time, `None` for no limit
+    limit: Option<usize>,
+}
+
+/// Slot of a host taken by `HostQueue::next`, freed when dropped.
+pub struct Slot<'a, T> {
+    queue: &'a HostQueue<T>,
+    host: String,
+}
+
+impl<T> Drop for Slot<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        if let Some(busy) = state.busy.get_mut(&self.host) {
+            *busy -= 1;
+        }
+        self.queue.freed.notify_all();
+    }
+}
+
+impl<T> HostQueue<T> {
+    /// Queue of `items` in their order, each with the host it connects to.
+    pub fn new(items: Vec<(String, T)>, limit: Option<usize>) -> HostQueue<T> {
+        HostQueue {
+            state: Mutex::new(State { items, busy: HashMap::new() }),
+            freed: Condvar::new(),
+            limit: limit.map(|limit| limit.max(1)),
+        }
+    }
+
+    /// Next item whose host has a free slot, blocks until there is one. `None` once the queue is
+    /// empty.
+    pub fn next(&self) -> Option<(Slot<'_, T>, T)> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.items.is_empty() {
+                return None;
+            }
+            let free = state.items.iter()
+                .position(|(host, _)| self.limit.is_none_or(|limit| state.busy.get(host).copied().unwrap_or(0) < limit));
+            if let Some(index) = free {
+                let (host, item) = state.items.remove(index);
+                *state.busy.entry(host.clone()).or_insert(0) += 1;
+                return Some((Slot { queue: self, host }, item));
+            }
+            state = self.freed.wait(state).unwrap();
+        }
+    }
+}
+
+/// Host of a git `url`, empty for local paths.
+pub fn host(url: &str) -> String {
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest,
+        // scp like syntax of ssh, `git@github.com:owner/repo`
+        None => match url.split_once(':') {
+            Some((host, _)) if !host.contains('/') => host,
+            _ => return String::new(),
+        },
+    };
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host = authority.rsplit_once('@').map(|(_, host)| host).unwrap_or(authority);
+    host.to_lowercase()
+}
@@ -2,22 +2,124 @@
 // remote endpoints to the current repository.
 
 use chrono::{
+    DateTime,
+    Duration,
     Local,
     LocalResult,
     TimeZone,
+    Utc,
+};
+use clap::{
+    CommandFactory,
+    Parser,
+    ValueEnum,
 };
-use clap::Parser;
-use git2;
 use octorust::{
-    types::ReposListForksSort,
+    types::{
+        CommitComparison,
+        CommitComparisonStatus,
+        CommitDataType,
+        FullRepository,
+        License,
+        MinimalRepository,
+        Order,
+        ReposListForksSort,
+        ReposListOrgSort,
+        ReposListOrgType,
+        SearchIssuesPullRequestsSort,
+    },
     Client,
+    ClientError,
     StatusCode,
 };
-use std::process::exit;
+use fuzzy_matcher::{
+    skim::SkimMatcherV2,
+    FuzzyMatcher,
+};
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::{
+    BTreeMap,
+    HashMap,
+    HashSet,
+};
+use std::fmt;
+use std::fs;
+use std::io::{
+    self,
+    stderr,
+    stdin,
+    stdout,
+    IsTerminal,
+    Write,
+};
+use std::future::Future;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::env;
+use std::process::{
+    exit,
+    Command,
+};
+use std::sync::{
+    atomic::{
+        AtomicBool,
+        AtomicU64,
+        Ordering,
+    },
+    mpsc,
+    Arc,
+};
+use std::thread;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+mod api;
+mod ci;
+mod completions;
+mod filter;
+mod gitconfig;
+mod hosts;
+mod oplog;
+mod pager;
+mod state;
+mod template;
+mod theme;
+
+use gitconfig::NewRemote;
+use oplog::OpLog;
+
+use theme::{
+    Palette,
+    Theme,
+};
+
+/// Exit code of `--check` if no fork matches.
+const EXIT_NO_MATCH: i32 = 3;
+
+/// Exit code if a git repository is needed, but there is none.
+const EXIT_NOT_IN_REPO: i32 = 4;
+
+/// Prefix of all remotes managed by rgf.
+const REMOTE_PREFIX: &str = "rgf__";
+
+/// Repository argument to read the repositories from stdin.
+const STDIN_REPOSITORY: &str = "-";
+
+/// Push url for added remotes, every push to such a remote fails.
+///
+/// A plain word like `no_push` would be taken for a relative path, so `git push` would write to a
+/// local repository of that name if there is one. git has no transport for the `no-push` scheme
+/// and fails right away, without touching the network or the file system.
+const NO_PUSH_URL: &str = "no-push://disabled";
 
+/// First wait before a git network operation is retried, doubled with every retry.
+const NETWORK_RETRY_WAIT: std::time::Duration = std::time::Duration::from_secs(2);
 
 #[derive(Parser, Debug)]
-#[command(version)]
+#[command(version, disable_version_flag = true)]
 #[command(about = "Add all forks of a github repository as remotes to the current repository")]
 struct Args {
     // Options
@@ -26,12 +128,244 @@ struct Args {
     #[clap(short, long)]
     dry_run: bool,
 
+    /// Print the operations of a dry run of `--add`, `--update` and `--sync` as one JSON document
+    ///
+    /// Implies `--dry-run`, the per remote lines and summaries are left out so stdout is only the
+    /// plan. Every operation has an `op` and the `remote`, `url`, `to` and `detail` if known:
+    /// `add` (with `url`), `skip` (with the reason as `detail`), `update` (with the commit range
+    /// as `detail`), `rename` (with the new name as `to` and `url`), `prune` and `fail` (with the
+    /// error as `detail`). `version` only changes when the schema does, a run without
+    /// `--dry-run(-json)` applies the plan. An update fetches nothing, the heads of the remotes
+    /// are only listed like `git ls-remote` does. Example:
+    ///
+    ///     $ rgf google/battery-historian --add --dry-run-json
+    ///     {"version":1,"operations":[{"op":"add","remote":"rgf__ntc-stefan_battery-historian",
+    ///     "url":"https://github.com/ntc-stefan/battery-historian.git"},{"op":"skip",
+    ///     "remote":"rgf__ippocratis_battery-historian","detail":"exists"}]}
+    #[clap(long, default_value = "false", verbatim_doc_comment, conflicts_with_all = ["plan", "follow_renamed_forks", "migrate_prefix", "dedup_by_content"])]
+    dry_run_json: bool,
+
 
     /// Add the forks to current repository as remotes
+    ///
+    /// Inside a linked worktree the remotes are added to the config of the main repository, so
+    /// they are visible from all worktrees.
     #[clap(short, long, default_value = "false")]
     add: bool,
 
-    /// Only list the forks, but do not add them as remotes. Sort order is newest first
+    /// Choose the forks to add from a list
+    ///
+    /// After filtering, all forks are shown with their stars and last push. Space toggles a fork,
+    /// enter adds the chosen ones, escape aborts without adding anything. Needs a terminal.
+    #[clap(long, requires = "add")]
+    interactive: bool,
+
+    /// Fetch refspec of the added or exported remotes, `{name}` is replaced by the remote name
+    ///
+    /// Decides where `git fetch <remote>` puts the refs of a fork. The default is the one git uses,
+    /// `+refs/heads/*:refs/remotes/{name}/*`. Example, fetch only the default branch:
+    ///
+    ///     $ rgf google/battery-historian --add --refspec '+refs/heads/master:refs/remotes/{name}/master'
+    #[clap(long, value_parser = parse_refspec, verbatim_doc_comment)]
+    refspec: Option<String>,
+
+    /// Only add forks which are at least this many commits ahead of their source
+    ///
+    /// Compares the default branch of every selected fork with the one of its source, one api
+    /// call per fork and `--jobs` at a time, plus one per source repository. Forks which could not
+    /// be compared, e.g. after hitting the rate limit, are not added. The number of dropped forks is
+    /// printed to stderr.
+    #[clap(long, requires = "add", value_name = "N")]
+    add_if_ahead: Option<u64>,
+
+    /// Skip forks without any commit ahead of their source, they are only stale copies
+    ///
+    /// Compares the forks like `--add-if-ahead` does, for `--list` as well as `--add`. Forks
+    /// which could not be compared are kept. The number of skipped forks is printed to stderr.
+    #[clap(long, default_value = "false")]
+    skip_forks_behind: bool,
+
+    /// Only keep forks which are ahead of, behind or diverged from their source
+    ///
+    /// Compares the forks like `--add-if-ahead` does. `ahead` forks only add commits, so their
+    /// changes can most likely be pulled without conflicts. Forks identical to their source or
+    /// which could not be compared are dropped, their number is printed to stderr. With
+    /// `--format json` every compared fork has its `divergence`.
+    #[clap(long, value_enum)]
+    class: Option<ForkClass>,
+
+    /// Only keep forks whose unique commits all have a signature github could verify
+    ///
+    /// Compares the forks like `--add-if-ahead` does, the unique commits are those ahead of the
+    /// source, so forks without any are kept. Forks with more unique commits than the 250 the
+    /// compare api lists, or which could not be compared are dropped, their number is printed to
+    /// stderr. With `--format json` the `divergence` of every compared fork lists its unique
+    /// commits with their `verified` flag.
+    #[clap(long, default_value = "false")]
+    only_verified: bool,
+
+    /// Show how far every listed fork diverged from its source
+    ///
+    /// Compares the forks like `--add-if-ahead` does. Every fork of `--list` is followed by
+    /// `+N/-M`, the number of commits it is ahead (green) and behind (red) of its source, and the
+    /// subjects of its newest unique commits, see `--max-commits`. Forks which could not be
+    /// compared show `?`. With `--format json` every compared fork has its `divergence` with
+    /// `ahead_by`, `behind_by` and the `subject` of every unique commit.
+    #[clap(long, default_value = "false")]
+    compare: bool,
+
+    /// Number of commit subjects shown per fork for `--compare`, 0 for only the counts
+    #[clap(long, default_value = "3", value_name = "N", requires = "compare")]
+    max_commits: usize,
+
+    /// Compare the forks with this branch, tag or commit of their source instead of its default branch
+    ///
+    /// Applies to `--add-if-ahead`, `--skip-forks-behind`, `--class`, `--only-verified` and
+    /// `--compare`. A full commit SHA pins the comparison, so runs at different times count
+    /// against the same point even as the source moves on. Abbreviated SHAs are refused, they may become ambiguous. The
+    /// commit is looked up once per source first, the forks of a source which does not know it are
+    /// not compared. Example:
+    ///
+    ///     $ rgf google/battery-historian --list --skip-forks-behind --compare-base 29a8e0c0ffee29a8e0c0ffee29a8e0c0ffee29a8
+    #[clap(long, value_name = "REF", value_parser = parse_compare_base, verbatim_doc_comment)]
+    compare_base: Option<CompareBase>,
+
+    /// Run this shell command for every added remote
+    ///
+    /// The name and url of the remote are passed as `$1` and `$2` and as `RGF_REMOTE_NAME` and
+    /// `RGF_REMOTE_URL`. A dry run prints the commands instead of running them. Failed commands
+    /// are counted as `hook-failed:N` in the summary. Example:
+    ///
+    ///     $ rgf google/battery-historian --add --on-add 'git fetch "$1"'
+    #[clap(long, requires = "add", value_name = "COMMAND", verbatim_doc_comment)]
+    on_add: Option<String>,
+
+    /// Write a shell script adding the selected forks as remotes instead of adding them
+    ///
+    /// The script can be reviewed and run in any repository later. It skips remotes which already
+    /// exist there and honors `--no-push` and `--refspec`. Example:
+    ///
+    ///     $ rgf google/battery-historian --export-script add-forks.sh
+    ///     Wrote 10 remotes to add-forks.sh
+    #[clap(long, value_name = "PATH", verbatim_doc_comment)]
+    export_script: Option<PathBuf>,
+
+    /// Print what an add would change compared to the current rgf remotes, without changing it
+    ///
+    /// Selected forks without a remote are `add`, selected forks with one are `keep`. Existing rgf
+    /// remotes of none of the fetched forks are `orphan`, their fork is gone or beyond the fetched
+    /// pages, see `--max-pages`. Forks dropped by filters are no orphans. With `--format json` the
+    /// three lists are printed as one object. Example:
+    ///
+    ///     $ rgf google/battery-historian --plan
+    ///     + rgf__ntc-stefan_battery-historian
+    ///     = rgf__ippocratis_battery-historian
+    ///     - rgf__goldjunge91_battery-historian
+    ///     add:1 keep:1 orphan:1
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    plan: bool,
+
+    /// Reconcile the rgf remotes with the forks on github, see `--source-of-truth`
+    ///
+    /// Prints the plan of `--plan` first and then applies it: missing remotes are added like with
+    /// `--add`, and with `--source-of-truth api` the orphans are removed. Nothing is applied with
+    /// `--dry-run`, with `--dry-run-json` the plan is printed as such. Removing needs a typed
+    /// confirmation or `--assume-yes-destructive`. Example:
+    ///
+    ///     $ rgf google/battery-historian --sync --source-of-truth api
+    ///     + rgf__ntc-stefan_battery-historian
+    ///     = rgf__ippocratis_battery-historian
+    ///     - rgf__goldjunge91_battery-historian
+    ///     add:1 keep:1 orphan:1
+    ///     Remote rgf__ntc-stefan_battery-historian added
+    ///     added:1 skipped:1 failed:0
+    ///     Remote rgf__goldjunge91_battery-historian removed
+    #[clap(long, default_value = "false", conflicts_with_all = ["add", "plan"], verbatim_doc_comment)]
+    sync: bool,
+
+    /// Which side wins in `--sync` if github and the rgf remotes disagree
+    ///
+    /// With `api` the rgf remotes of the listed repositories become exactly their fetched forks,
+    /// the others are removed. Remotes of other repositories and of forks dropped by filters are
+    /// kept. If the fork list may be incomplete, because of `--page`, a full last page of
+    /// `--max-pages`, a rate limit or a repository which could not be listed, nothing is removed.
+    /// With `git` remotes are only added, never removed.
+    #[clap(long, value_enum, default_value = "git", requires = "sync")]
+    source_of_truth: SourceOfTruth,
+
+    /// Only print the final `added:N skipped:N failed:N` summary of an add
+    ///
+    /// The line per remote is suppressed, errors are still printed to stderr.
+    #[clap(long, default_value = "false", requires = "add")]
+    summary_only: bool,
+
+    /// Disable pushing to the added remotes
+    ///
+    /// The push url of every added remote is set to `no-push://disabled`, so an accidental
+    /// `git push` to a fork fails instead of writing to someone else's repository. Use
+    /// `--no-push=false` to keep the push url of the fork.
+    #[clap(long, default_value = "true", default_missing_value = "true", num_args = 0..=1, require_equals = true, action = clap::ArgAction::Set)]
+    no_push: bool,
+
+    /// Put the source repository into the names of the remotes
+    ///
+    /// Forks of several repositories, e.g. with `--org` or a list on stdin, can end up with the
+    /// same `rgf__<owner>_<repo>` name. With this option the remotes are named
+    /// `rgf__<source-owner>_<source-repo>__<owner>_<repo>` instead. They are still rgf remotes for
+    /// all other modes, e.g. `--update --prune` and `--follow-renamed-forks`.
+    #[clap(long, default_value = "false")]
+    add_prefix_per_source: bool,
+
+    /// Longest name of a remote in bytes, longer names are truncated
+    ///
+    /// Every remote is a directory `refs/remotes/<name>`, and most filesystems allow only 255
+    /// bytes per path component. A longer name is cut and gets `-` and 8 hex digits of a hash of
+    /// the whole name, so the truncated names of different forks stay different and an add finds
+    /// the same remote again. An add warns about every truncated name.
+    #[clap(long, value_name = "N", default_value = "200", value_parser = clap::value_parser!(u16).range(16..))]
+    max_name_length: u16,
+
+    /// Do not add forks which already are a remote under another name
+    ///
+    /// Remotes added by hand before rgf was used point at the same forks. Every remote of the
+    /// repository, rgf or not, is compared by its url, ignoring the protocol, a trailing `.git`
+    /// and case. Such forks are skipped and printed as `= (existing non-rgf) <remote>`, or as
+    /// `= (existing) <remote>` for an rgf remote of another name, e.g. of `--add-prefix-per-source`.
+    #[clap(long, default_value = "false", requires = "add")]
+    dedupe_remotes: bool,
+
+    /// Canonicalize the urls of added remotes
+    ///
+    /// The host is lowercased, trailing slashes are removed and the url ends with exactly one
+    /// `.git`, so the remotes of rgf all look the same whatever github returned. Also applies to
+    /// `--export-script`.
+    #[clap(long, default_value = "false")]
+    normalize_urls: bool,
+
+    /// Use the ssh urls of the forks instead of the https ones
+    ///
+    /// Applies to added remotes, `--export-script` and `--urls-only`.
+    #[clap(long, default_value = "false")]
+    ssh: bool,
+
+    /// Config file the added remotes are written to
+    ///
+    /// The local config of the repository is shared by all its worktrees, also when rgf runs in
+    /// a linked worktree. `worktree` keeps the remotes to the current worktree, which needs
+    /// `extensions.worktreeConfig` to be enabled. libgit2 does not read the config of a worktree,
+    /// so `--update`, `--sync` and the other operations on existing remotes only see those of the
+    /// local config. The global config is not offered, remotes there would show up in every
+    /// repository of the user.
+    #[clap(long, value_enum, default_value = "local")]
+    git_config_scope: gitconfig::Scope,
+
+    /// Store the github description of each added fork as `remote.<name>.description`
+    ///
+    /// Shown by `--list-remotes --verbose`. Forks without a description get none.
+    #[clap(long, default_value = "false")]
+    store_description: bool,
+
+    /// Only list the forks, but do not add them as remotes. Sort order is `--sort`
     ///
     /// Just a list of forks and their own fork count is printed. Example:
     ///
@@ -46,6 +380,376 @@ struct Args {
     #[clap(short, long, default_value = "false", verbatim_doc_comment)]
     list: bool,
 
+    /// Also list the source repository itself, in front of its forks
+    ///
+    /// Its stats are fetched with one extra api call per source repository, so the forks can be
+    /// compared with it. In text it is marked with `(source)`, in JSON its `role` is `source`
+    /// instead of `fork`.
+    #[clap(long, default_value = "false", requires = "list")]
+    include_source: bool,
+
+    /// Only check whether any fork matches the filters, for scripts and CI jobs
+    ///
+    /// Nothing is printed unless `--verbose` is given. The exit code is 0 if at least one fork
+    /// matches and 3 if none does. Example, alert on new forks of the last week:
+    ///
+    ///     $ rgf google/battery-historian --check --created-since 7d && echo "new forks"
+    #[clap(long, default_value = "false", conflicts_with_all = ["list", "add"], verbatim_doc_comment)]
+    check: bool,
+
+    /// Print more details about what is done
+    ///
+    /// Every api request is printed to stderr with its url, its status and the remaining quota
+    /// github reports for it, e.g. `GET
+    /// https://api.github.com/repos/google/battery-historian/forks?page=1&per_page=10&sort=newest
+    /// 200 remaining:4987`. The token is never printed, secrets in urls are shown as `***`.
+    #[clap(short, long, default_value = "false")]
+    verbose: bool,
+
+    /// Only consider forks created within this duration, e.g. `12h`, `7d` or `2w`
+    ///
+    /// Forks without a creation date are skipped.
+    #[clap(long, value_parser = parse_duration)]
+    created_since: Option<Duration>,
+
+    /// Only consider forks of the owners listed in this file
+    ///
+    /// One owner login per line, empty lines and lines starting with `#` are ignored. Logins are
+    /// compared case insensitive, like github does. The allowlist is applied before all other
+    /// filters, a fork of an owner not listed is never considered.
+    #[clap(long, value_name = "PATH")]
+    owner_allowlist_file: Option<PathBuf>,
+
+    /// Only consider forks which are not in this earlier `--list --format json` listing
+    ///
+    /// Forks are compared by `full_name`, ignoring case, so only the forks which appeared since
+    /// the listing was saved are left. To roll the snapshot forward, save a listing without this
+    /// option after processing the new forks. Example:
+    ///
+    ///     $ rgf google/battery-historian --list --only-new-since forks.json
+    ///     $ rgf google/battery-historian --list --format json > forks.json
+    #[clap(long, value_name = "PATH", verbatim_doc_comment)]
+    only_new_since: Option<PathBuf>,
+
+    /// Write the names of all listed forks to this file, to be read by `--only-new-since`
+    ///
+    /// Written right after listing, before any filter applies, as JSON array of objects with a
+    /// `full_name` like the listing of `--format json`.
+    #[clap(long, value_name = "PATH")]
+    save_snapshot: Option<PathBuf>,
+
+    /// Repeat the run every this long until Ctrl-C, e.g. `--list --every 1h`
+    ///
+    /// Every cycle is a run of rgf of its own with all other options, a failing cycle does not
+    /// stop the following ones. If the rate limit is used up, the next cycle waits for its reset.
+    #[clap(long, value_parser = parse_duration)]
+    every: Option<Duration>,
+
+    /// Only consider the forks which are new since the previous cycle of `--every`
+    ///
+    /// The first cycle considers all forks. Works like `--only-new-since` with a snapshot in the
+    /// state directory, which is removed on Ctrl-C.
+    #[clap(long, default_value = "false", requires = "every", conflicts_with = "only_new_since")]
+    only_new: bool,
+
+    /// Only consider forks owned by users or by organizations
+    #[clap(long, value_enum)]
+    owner_type: Option<OwnerType>,
+
+    /// Only consider forks of at most this size in KB, as reported by github
+    #[clap(long)]
+    max_size: Option<i64>,
+
+    /// Only consider forks matching this expression
+    ///
+    /// Fields are compared with `==`, `!=`, `<`, `<=`, `>`, `>=` and combined with `&&`, `||`,
+    /// `!` and parentheses. Strings are compared case insensitive, dates are given as strings.
+    /// Known fields: stars, forks, watchers, open_issues, size, name, owner, language, license,
+    /// description, default_branch, pushed_at, created_at, updated_at, archived, disabled.
+    /// Forks without a value for a compared field, e.g. without a language, do not match.
+    /// Example:
+    ///
+    ///     $ rgf google/battery-historian --filter-expr 'stars > 10 && pushed_at > "2024-01-01" && language == "Go"'
+    #[clap(long, value_parser = filter::parse, verbatim_doc_comment)]
+    filter_expr: Option<filter::Expr>,
+
+    /// Only consider forks with at least this many forks of their own
+    ///
+    /// Forks which were forked again are often the maintained ones, `--sort forks` puts the most
+    /// forked first. The number of dropped forks is printed to stderr.
+    #[clap(long)]
+    min_forks_count: Option<i64>,
+
+    /// Do not list forks which were never forked themselves
+    ///
+    /// These are often the vast majority of unchanged mirrors. Only `--list` is affected, the
+    /// number of hidden forks is printed to stderr.
+    #[clap(long, default_value = "false", requires = "list")]
+    hide_zero_forks: bool,
+
+    /// Only consider forks whose name fuzzy matches this query, best matches first
+    ///
+    /// The characters of the query have to appear in order in `<owner>/<repo>`, but not next to
+    /// each other, e.g. `gjng` matches `goldjunge91/battery-historian`. Case is ignored unless the
+    /// query contains upper case letters.
+    #[clap(long)]
+    search: Option<String>,
+
+    /// Count the contributors of every selected fork
+    ///
+    /// Costs one api call per fork, `--jobs` of them run at a time. If the rate limit is hit, the
+    /// remaining forks are left without a count. The count is appended to the `--list` lines and
+    /// part of the JSON output.
+    #[clap(long, default_value = "false")]
+    contributors: bool,
+
+    /// Look up every fork on its own to fill in fields the fork listing of github leaves empty
+    ///
+    /// The listing does not always include e.g. the language, license or description of a fork.
+    /// With this option they are taken from the full repository of the fork before any filter
+    /// runs, so `--filter-expr`, `--count-by` and `--store-description` see them. Costs one api
+    /// call per fetched fork, `--jobs` of them run at a time, each fork is only looked up once.
+    #[clap(long, default_value = "false")]
+    enrich: bool,
+
+    /// Get the last commit on the default branch of every selected fork
+    ///
+    /// More precise than `pushed_at`, which also changes for pushes to other branches. Costs one
+    /// api call per fork, `--jobs` of them run at a time. If the rate limit is hit, the remaining
+    /// forks are left without one. Its author and date are appended to the `--list` lines and
+    /// part of the JSON output as `last_commit`.
+    #[clap(long, default_value = "false")]
+    last_commit: bool,
+
+    /// Process the per fork api calls in batches of this many forks, with a pause between them
+    ///
+    /// Applies to `--contributors`, `--last-commit` and `--add-if-ahead`, `--jobs` calls of a
+    /// batch run at a time. Bursts of many calls can run into the secondary rate limits of
+    /// github, which then pauses all calls for a minute or more. Off by default, all forks are
+    /// one batch. For hundreds of forks `--batch-size 100` keeps the bursts small.
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    batch_size: Option<u32>,
+
+    /// Pause between two batches of `--batch-size`, e.g. `10s` or `1m`
+    #[clap(long, value_parser = parse_duration, default_value = "10s", requires = "batch_size")]
+    batch_delay: Duration,
+
+    /// Send at most this many api requests per minute
+    ///
+    /// The requests are spaced evenly, also those of `--jobs` running at the same time and the
+    /// retries. Unlike the pauses after a rate limit was hit this keeps rgf below the secondary
+    /// rate limits of github to begin with, whatever quota is left. Off by default.
+    #[clap(long, value_name = "PER_MINUTE", value_parser = clap::value_parser!(u32).range(1..))]
+    limit_rate: Option<u32>,
+
+    /// Count the pull requests the owner of every selected fork opened against its source
+    ///
+    /// Forks whose owners contribute back are often the relevant ones. Every count is one call
+    /// of the search api, which allows only 30 calls per minute with a token, so this is meant
+    /// for few forks or together with `--batch-size`. The count is appended to the `--list` lines
+    /// and part of the JSON output as `upstream_prs`.
+    #[clap(long, default_value = "false")]
+    upstream_prs: bool,
+
+    /// Only consider forks whose owner opened at least this many pull requests against the
+    /// source, implies `--upstream-prs`
+    ///
+    /// Forks whose count is unknown are skipped. The number of dropped forks is printed to stderr.
+    #[clap(long)]
+    min_upstream_prs: Option<u64>,
+
+    /// Fetch the topics of every selected fork
+    ///
+    /// One api call per fork. The topics are appended to the `--list` lines and part of the JSON
+    /// output as `topics`.
+    #[clap(long, default_value = "false")]
+    topics: bool,
+
+    /// Color the `--list` lines of forks sharing a topic with their source, implies `--topics`
+    ///
+    /// Such forks are likely still about what the source is about. Costs one more api call per
+    /// source for its topics.
+    #[clap(long, default_value = "false")]
+    highlight_shared_topics: bool,
+
+    /// Show the `parent` and the `source` of every selected fork
+    ///
+    /// The parent is the repository a fork was made from, the source the one at the top of the
+    /// fork network. Both differ for a fork of a fork. The `--list` lines get the parent, followed
+    /// by the source if it is another one, e.g. `ntc-stefan/battery-historian (source
+    /// google/battery-historian)`, the JSON output gets both as `relationship`. Costs one api call
+    /// per fork, none with `--enrich` which looks up the forks anyway.
+    #[clap(long, default_value = "false")]
+    relationships: bool,
+
+    /// Give up on a single fork after this duration, e.g. `30s` or `2m`
+    ///
+    /// Applies to every api call made per fork, like the one of `--contributors`. A fork which
+    /// times out is reported on stderr and treated like one whose call failed, the other forks
+    /// are still processed. No timeout by default.
+    #[clap(long, value_parser = parse_duration)]
+    timeout_per_fork: Option<Duration>,
+
+    /// Only consider forks with at least this many contributors, implies `--contributors`
+    ///
+    /// Forks whose count is unknown are skipped. The number of dropped forks is printed to stderr.
+    #[clap(long)]
+    min_contributors: Option<u64>,
+
+    /// Print statistics of the selected forks
+    ///
+    /// Example:
+    ///     $ rgf google/battery-historian --stats
+    ///     forks:10 size:43310KB
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    stats: bool,
+
+    /// Print the number of selected forks per language, owner type or license
+    ///
+    /// The most common buckets come first. Forks without the field are counted as `unknown`. With
+    /// `--format json` a map from bucket to count is printed. Example:
+    ///
+    ///     $ rgf google/battery-historian --count-by language
+    ///     Go | 6
+    ///     unknown | 3
+    ///     JavaScript | 1
+    #[clap(long, value_enum, verbatim_doc_comment)]
+    count_by: Option<CountBy>,
+
+    /// Print per owner statistics of the selected forks, most starred owners first
+    ///
+    /// Sums up the forks and stars of every owner and shows their most recent push. Example:
+    ///
+    ///     $ rgf --org google --owner-stats
+    ///     ntc-stefan | forks:2 stars:14 last-push:2024-03-01
+    ///     ippocratis | forks:1 stars:3 last-push:2023-12-02
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    owner_stats: bool,
+
+    /// Print the selected forks grouped by how recently they were pushed to
+    ///
+    /// The tiers are set by `--activity-tiers`, forks pushed longer ago or never are `stale`. With
+    /// `--format json` a map from tier to forks is printed. Example:
+    ///
+    ///     $ rgf google/battery-historian --group-by-activity
+    ///     # pushed within 7d
+    ///     ntc-stefan/battery-historian | 0
+    ///     # pushed within 30d
+    ///     # pushed within 365d
+    ///     ippocratis/battery-historian | 0
+    ///     # stale
+    ///     314937885/battery-historian | 0
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    group_by_activity: bool,
+
+    /// Upper bounds of the tiers of `--group-by-activity`, comma separated, shortest first
+    #[clap(long, value_delimiter = ',', default_value = "7d,30d,365d", value_parser = parse_tier, requires = "group_by_activity")]
+    activity_tiers: Vec<(String, Duration)>,
+
+    /// Output format of `--list`, `--list-remotes`, `--count-by`, `--owner-stats`,
+    /// `--group-by-activity` and `--version`
+    #[clap(long, value_enum, default_value = "text")]
+    format: Format,
+
+    /// Columns of the `--list` text output and their order, comma separated
+    ///
+    /// The columns are headed by their names and aligned like with `--aligned`. Without this option the name and
+    /// the number of forks are printed, followed by the other values if they are fetched.
+    /// `contributors`, `last_commit`, `upstream_prs` and `topics` fetch their data like the
+    /// options of the same name, `relationship` like `--relationships`. Example:
+    ///
+    ///     $ rgf google/battery-historian --list --columns name,stars,pushed_at
+    ///     name                                 | stars | pushed_at
+    ///     ntc-stefan/battery-historian         | 0     | 2024-01-02T10:00:00+00:00
+    ///     goldjunge91/battery-historian        | 3     | 2023-11-20T08:12:45+00:00
+    #[clap(long, value_enum, value_delimiter = ',', requires = "list", verbatim_doc_comment)]
+    columns: Option<Vec<Column>>,
+
+    /// Align the columns of the `--list` and `--list-remotes` text output
+    ///
+    /// Every column is padded to its widest value. Only if the output is a terminal, piped
+    /// output keeps the plain ` | ` separators, which are simpler to parse.
+    #[clap(long, default_value = "false")]
+    aligned: bool,
+
+    /// Print only the url of every listed fork, one per line
+    ///
+    /// All filters and the sort order apply as for the other `--list` output, the source is not
+    /// printed. Meant for piping to other tools:
+    ///
+    ///     $ rgf google/battery-historian --list --urls-only --filter-expr 'stars >= 5' | xargs -n1 git clone
+    #[clap(long, default_value = "false", requires = "list", conflicts_with_all = ["columns", "output_template_file"], verbatim_doc_comment)]
+    urls_only: bool,
+
+    /// Render the `--list` text output with the template in this file
+    ///
+    /// The text between `{{#forks}}` and `{{/forks}}` is repeated for every listed fork, the
+    /// text before and after it is printed once as header and footer. Inside the section
+    /// `{{source}}`, `{{url}}` and all names of `--columns` are replaced with the values of the
+    /// fork, e.g. `{{stars}}`. `contributors`, `last_commit`, `upstream_prs`, `topics` and
+    /// `relationship` fetch their data like `--columns` does. The header and footer know `{{sources}}` and `{{count}}`, the
+    /// number of listed forks. Values are inserted as they are, without any escaping. The
+    /// template is checked before anything is fetched. Example of a Markdown report:
+    ///
+    ///     # Forks of {{sources}}
+    ///
+    ///     {{#forks}}
+    ///     - [{{name}}]({{url}}): {{stars}} stars, last push {{pushed_at}}
+    ///     {{/forks}}
+    ///
+    ///     {{count}} forks
+    #[clap(long, value_name = "PATH", value_parser = read_template, requires = "list", conflicts_with = "columns", verbatim_doc_comment)]
+    output_template_file: Option<template::Template>,
+
+    /// Indent the JSON of `--format json` for reading it in a terminal
+    ///
+    /// Without it the JSON is printed on one line, for piping it into other tools.
+    #[clap(long, default_value = "false")]
+    json_pretty: bool,
+
+    /// Shorten names and urls in the text output of `--list` and `--list-remotes` to this many characters
+    ///
+    /// Longer ones are cut and end with `…`, so wide listings do not wrap in narrow terminals.
+    /// JSON output and the remotes which are added always use the full names and urls.
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u16).range(2..))]
+    abbrev: Option<u16>,
+
+    /// Do not write the job summary when running in GitHub Actions
+    ///
+    /// If `GITHUB_STEP_SUMMARY` is set, the forks of `--list` and the result of `--add` are
+    /// appended to it as markdown tables, shown on the summary page of the job.
+    #[clap(long, default_value = "false")]
+    no_ci_summary: bool,
+
+    /// Do not page long listings
+    ///
+    /// Listings longer than the terminal are shown through `$PAGER` (default `less -R`) if the
+    /// output is a terminal.
+    #[clap(long, default_value = "false")]
+    no_pager: bool,
+
+    /// Order in which github returns the forks
+    ///
+    /// The order decides which forks are on the fetched pages, so `--page` and `--per-page` pick
+    /// e.g. the most starred forks with `stargazers`.
+    #[clap(long, value_enum, default_value = "newest")]
+    sort: ForkSort,
+
+    /// Direction of `--sort`, applied by rgf to the fetched forks
+    ///
+    /// Defaults to `desc` for `newest`, `stargazers`, `watchers` and `forks`, and to `asc` for
+    /// `oldest` and `name`, so `--sort oldest --sort-dir desc` is the same as `--sort newest`. With
+    /// `--search` the best matches still come first.
+    #[clap(long, value_enum)]
+    sort_dir: Option<SortDir>,
+
+    /// Sort forks with the same `--sort` key by name, for output which can be diffed
+    ///
+    /// The order of github is not deterministic for forks with equal keys, e.g. many with 0 stars.
+    /// Always on with `--format json`.
+    #[clap(long, default_value = "false")]
+    stable: bool,
+
     /// Number of remotes to be added or listed
     #[clap(long, default_value = "10")]
     per_page: u16,
@@ -54,6 +758,28 @@ struct Args {
     #[clap(long, default_value = "1")]
     page: u16,
 
+    /// Number of pages of forks to fetch, starting at `--page`
+    ///
+    /// Every page is one api call, so this bounds the api calls of a run precisely. Fetching
+    /// stops early at the last page of forks.
+    #[clap(long, default_value = "1", value_parser = clap::value_parser!(u16).range(1..))]
+    max_pages: u16,
+
+    /// Check the setup and print a checklist, e.g. before the first real run
+    ///
+    /// Checks that a token is given and accepted by github, that the api can be reached, that
+    /// quota is left and that rgf runs inside a git repository. Every failed check comes with a
+    /// hint how to fix it, the exit code is 1 if any check fails. Example:
+    ///
+    ///     $ rgf --doctor
+    ///     [ok]   token: given
+    ///     [ok]   api: reachable, token accepted
+    ///     [ok]   rate limit: 4999/5000 available
+    ///     [fail] git repository: not inside a git repository
+    ///            hint: run rgf inside the repository to add the forks to, or pass --repo-path
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    doctor: bool,
+
     /// View current rate limit status
     ///
     /// Output of this option is the current rate limit status of the github api.
@@ -62,135 +788,4128 @@ struct Args {
     #[clap(long, default_value = "false", verbatim_doc_comment)]
     rate_limit: bool,
 
-    /// Github token for authentication
-    #[clap(short, long, env="GITHUB_TOKEN")]
-    token: Option<String>,
+    /// Query and reprint the rate limit every this many seconds until Ctrl-C
+    ///
+    /// Handy to watch the quota in another terminal while a large run is going on.
+    #[clap(long, requires = "rate_limit", value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    watch: Option<u64>,
 
-    // Arguments
+    /// Directory for cache and state files
+    ///
+    /// All persistent files of rgf are stored below this directory. It is created if it does not
+    /// exist. Defaults to the platform cache directory, e.g. `~/.cache/rgf` on Linux.
+    #[clap(long, env = "RGF_STATE_DIR")]
+    state_dir: Option<PathBuf>,
 
-    /// The repository from which the forks are to be fetched
-    repository: String,
-}
+    /// List or add the forks of all repositories of an organization instead of a single repository
+    ///
+    /// Only the source repositories of the organization are considered, its own forks are
+    /// skipped. `--page` and `--per-page` apply to the forks of every repository. The listing is
+    /// grouped by repository:
+    ///
+    ///     $ rgf --org google --list
+    ///     # google/battery-historian
+    ///     ntc-stefan/battery-historian | 0
+    ///     ...
+    #[clap(long, conflicts_with = "repository", verbatim_doc_comment)]
+    org: Option<String>,
 
-#[derive(Debug)]
-pub struct OwnerRepo {
-    pub owner: String,
-    pub repo: String,
-}
+    /// Use the forks of the root of the fork network instead of the forks of the given fork
+    ///
+    /// The repository argument may be any fork, without `--root` its own forks are listed. With
+    /// it the `source` of the fork, the repository at the top of the network, is used and else its
+    /// `parent`. A repository which is no fork is used as it is, with a note on stderr. Not for a
+    /// list on stdin. Example:
+    ///
+    ///     $ rgf ntc-stefan/battery-historian --root --list -v
+    ///     Root of ntc-stefan/battery-historian: google/battery-historian
+    ///     ntc-stefan/battery-historian | 0
+    ///     ...
+    #[clap(long, default_value = "false", requires = "repository", verbatim_doc_comment)]
+    root: bool,
 
-impl OwnerRepo {
-    pub fn new(orinput: &String) -> Result<OwnerRepo, String> {
-        let parts: Vec<&str> = orinput.split('/').collect();
-        if parts.len() != 2 {
-            return Err("Invalid repository format".to_string());
-        }
-        Ok(OwnerRepo {
-            owner: parts[0].to_string(),
-            repo: parts[1].to_string(),
-        })
-    }
-}
+    /// Color theme of the output
+    ///
+    /// `auto` picks the palette matching the background of the terminal. Colors are disabled if
+    /// the output is no terminal or `NO_COLOR` is set.
+    #[clap(long, value_enum, default_value = "auto")]
+    theme: Theme,
 
+    /// Disable colored output, same as `--theme none`
+    #[clap(long)]
+    no_color: bool,
 
-fn to_credential(tok: Option<String>) -> Option<octorust::auth::Credentials> {
-    match tok {
-        Some(token) => Some(octorust::auth::Credentials::Token(token.clone())),
-        None => None,
-    }
-}
+    /// Check that all rgf remotes of the current repository are reachable
+    ///
+    /// Connects to every `rgf__` remote like `git ls-remote` does, but without fetching anything.
+    /// Dead or deleted forks are reported as unreachable. Works without a repository argument.
+    /// Example:
+    ///
+    ///     $ rgf --verify-remotes
+    ///     reachable rgf__ntc-stefan_battery-historian
+    ///     unreachable rgf__ippocratis_battery-historian: remote authentication required but no callback set
+    ///     reachable:1 unreachable:1
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    verify_remotes: bool,
 
-fn unify_remote_name(name: &String) -> String {
-    let mut out: String = name.clone();
-    out.insert_str(0, "rgf__");
-    out.replace("/", "_")
-}
+    /// Fetch all rgf remotes and report which of them advanced
+    ///
+    /// Only new objects are fetched, with the refspecs of each remote. A remote is `updated` if
+    /// the commit of its `HEAD` (or `main`/`master`) changed, `--jobs` remotes are fetched at a
+    /// time. The summary ends with the bytes received by all fetches together. Works without a
+    /// repository argument. Example:
+    ///
+    ///     $ rgf --update
+    ///     updated rgf__ntc-stefan_battery-historian 29a8e0c..1f2e3d4
+    ///     unchanged rgf__314937885_battery-historian
+    ///     failed rgf__ippocratis_battery-historian: remote authentication required but no callback set
+    ///     updated:1 unchanged:1 failed:1 received:1.4MiB
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    update: bool,
 
-#[tokio::main]
-async fn main() {
-    let args: Args = Args::parse();
+    /// Fetch only the last DEPTH commits of every branch with `--update`
+    ///
+    /// Saves bandwidth and time when mirroring hundreds of large forks of which only the recent
+    /// history matters. Combined with a `--refspec` of a single branch only that branch is
+    /// fetched. Example:
+    ///
+    ///     $ rgf --update --shallow 1
+    #[clap(long, requires = "update", value_name = "DEPTH", value_parser = clap::value_parser!(i32).range(1..), verbatim_doc_comment)]
+    shallow: Option<i32>,
 
-    let owner_repo = OwnerRepo::new(&args.repository).expect("Invalid repository format: gh standartformat is <owner>/<repo>");
+    /// Remove the remotes of deleted forks while running `--update`
+    ///
+    /// A fork counts as deleted if the fetch fails because the repository is not found or asks
+    /// for authentication, which is what github answers for a deleted public repository. Before
+    /// a remote is removed its fork is looked up on github by the url of the remote, or by its
+    /// name for other urls. If the fork was renamed the remote is renamed as well and fetched
+    /// again, its fetched commits are kept.
+    #[clap(long, default_value = "false", requires = "update")]
+    prune: bool,
 
-    let client = Client::new("myAgent", to_credential(args.token)).expect("Failed to create gh client");
+    /// Update rgf remotes whose fork was renamed or moved to another owner
+    ///
+    /// Looks up the fork of every `rgf__` remote by the owner and name in its url. If github
+    /// redirects to another repository, the url of the remote is updated and the remote is renamed
+    /// to match. Deleted forks are reported. Works without a repository argument, meant to be run
+    /// from time to time. Example:
+    ///
+    ///     $ rgf --follow-renamed-forks
+    ///     renamed rgf__ippocratis_battery-historian -> rgf__ippocratis_bh
+    ///     renamed:1 unchanged:4 failed:0
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    follow_renamed_forks: bool,
 
-    if args.rate_limit {
-        let rate_limit = match client.rate_limit().get().await {
-            Ok(response) => {
-                if response.status == StatusCode::OK {
-                    response.body
-                } else {
-                    panic!("Response Status not okay: {}", response.status);
-                }
-            },
-            Err(e) => {
-                println!("Error: {}", e);
-                exit(1);
-            }
-        };
-        // let x = Local.timestamp_opt(rate_limit.rate.reset, 0);
-        let dt = match Local.timestamp_opt(rate_limit.rate.reset, 0) {
-            // Some problems, just give the number back as string
-            LocalResult::None => rate_limit.rate.reset.to_string(),
-            LocalResult::Ambiguous(_, _) => rate_limit.rate.reset.to_string(),
-            // Clearly identifiable time. Format as rfc2822
-            LocalResult::Single(dt) => dt.to_rfc2822(),
-        };
-        println!("rate-limit:{}/{} available:{} reset-at:{}",
-            rate_limit.rate.used,
-            rate_limit.rate.limit,
-            rate_limit.rate.remaining,
-            dt);
-    }
+    /// Rename all remotes starting with `<OLD>` to start with `<NEW>` instead
+    ///
+    /// For remotes added under another prefix, e.g. by hand or by a script, so they become rgf
+    /// remotes without adding them again. Urls and fetched refs are kept, fetch refspecs other
+    /// than the default one have to be updated by hand and are reported. Example:
+    ///
+    ///     $ rgf --migrate-prefix fork_ rgf__
+    ///     renamed fork_ntc-stefan_battery-historian -> rgf__ntc-stefan_battery-historian
+    ///     renamed:1 failed:0
+    #[clap(long, num_args = 2, value_names = ["OLD", "NEW"], verbatim_doc_comment)]
+    migrate_prefix: Option<Vec<String>>,
 
-    let forks = match client.repos().list_forks(&owner_repo.owner, &owner_repo.repo, ReposListForksSort::Newest, args.per_page as i64, args.page as i64 ).await {
-        Ok(response) => {
-            if response.status == StatusCode::OK {
-                response.body
-            } else {
-                panic!("Response Status not okay: {}", response.status);
-            }
-        },
-        Err(e) => {
-            println!("Error: {}", e);
+    /// Group fetched rgf remotes which point at the same commit
+    ///
+    /// Many forks are plain mirrors without any change. Remotes are compared by the commit of
+    /// their `HEAD` (or `main`/`master`), so they have to be fetched before, e.g. with
+    /// `git fetch --all`. Every group of identical remotes is printed as one line:
+    ///
+    ///     $ rgf --dedup-by-content
+    ///     = 29a8e0c rgf__314937885_battery-historian rgf__ippocratis_battery-historian
+    ///     not fetched: rgf__goldjunge91_battery-historian
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    dedup_by_content: bool,
+
+    /// Check how the fetched branch of a remote would merge into `HEAD`, without merging
+    ///
+    /// The merge is done in memory against the `HEAD` (or `main`/`master`) of the remote, the
+    /// working tree and the index are not touched. Prints whether `HEAD` is up to date, could be
+    /// fast-forwarded, would merge cleanly or would conflict, together with the conflicting
+    /// paths. The number of commits the remote has over `HEAD` is shown, with `--verbose` the
+    /// commits are listed. The remote has to be fetched before, e.g. with `--update`. Example:
+    ///
+    ///     $ rgf --merge-preview rgf__ntc-stefan_battery-historian
+    ///     conflict rgf__ntc-stefan_battery-historian: 2 paths, 14 commits
+    ///       README.md
+    ///       bugreport/parse.go
+    #[clap(long, value_name = "REMOTE", verbatim_doc_comment)]
+    merge_preview: Option<String>,
+
+    /// Follow only the first parent of merges when counting the commits of `--merge-preview`
+    ///
+    /// Like `git log --first-parent`, the commits merged into the mainline of the fork are not
+    /// counted. Only applies to the comparison with the local clone, the counts of the github
+    /// api like `--compare-forks` always include all commits.
+    #[clap(long, default_value = "false", requires = "merge_preview")]
+    first_parent: bool,
+
+    /// Remove all but the first remote of each group found by `--dedup-by-content`
+    #[clap(long, default_value = "false", requires = "dedup_by_content")]
+    prune_dupes: bool,
+
+    /// Remove remotes of `--prune` and `--prune-dupes` without asking
+    ///
+    /// Adding remotes never asks, removing them does: the remotes to remove are listed first and
+    /// only removed once `remove` is typed. Without a terminal nothing is removed unless this
+    /// option is given, so a script has to opt in to removing remotes explicitly. `--dry-run`
+    /// neither asks nor removes anything.
+    #[clap(long, default_value = "false")]
+    assume_yes_destructive: bool,
+
+    /// List the rgf remotes of the current repository and their urls
+    ///
+    /// Works without a repository argument and without the github api. Example:
+    ///
+    ///     $ rgf --list-remotes
+    ///     rgf__ntc-stefan_battery-historian | https://github.com/ntc-stefan/battery-historian.git
+    ///
+    /// With `--verbose` the description stored by `--store-description` follows as third column.
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    list_remotes: bool,
+
+    /// Show the `owner/repo` of every remote of `--list-remotes` next to its name
+    ///
+    /// Taken from the github url of the remote. For other urls the name is turned back, which is
+    /// unambiguous for the names rgf gives, github owners can not contain `_`. Remotes of
+    /// `--add-prefix-per-source` with such an url are left without one. Part of the JSON output
+    /// as `full_name`.
+    #[clap(long, default_value = "false", requires = "list_remotes")]
+    strip_prefix_on_list: bool,
+
+    /// Git repository to work on instead of the one of the current directory
+    ///
+    /// Like for git itself, any directory inside the repository or a worktree works.
+    #[clap(long)]
+    repo_path: Option<PathBuf>,
+
+    /// Number of parallel jobs for network operations on remotes and per fork api calls
+    ///
+    /// If github answers with a secondary rate limit for too many concurrent requests, all jobs
+    /// pause for the time github asks for before they continue.
+    #[clap(short, long, default_value = "4")]
+    jobs: usize,
+
+    /// Number of the `--jobs` of `--verify-remotes` and `--update` that connect to the same host
+    ///
+    /// Nearly all remotes are on github.com, so by default all jobs may connect there at once.
+    /// With a limit the other jobs go on with the remotes of other hosts, or wait if there are
+    /// none. A limit above `--jobs` has no effect.
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_concurrent_hosts: Option<u32>,
+
+    /// Process forks and remotes in random order instead of the api order
+    ///
+    /// Spreads the concurrent per fork api calls, `--verify-remotes` and `--update` over the
+    /// forks, instead of working through e.g. the newest ones first. Only the processing order
+    /// changes, the output is printed in the same order as without it. The seed is printed with
+    /// `--verbose`.
+    #[clap(long, default_value = "false")]
+    randomize_order: bool,
+
+    /// Seed of `--randomize-order`, to get the same order again
+    ///
+    /// A new one is drawn for every run without it.
+    #[clap(long, requires = "randomize_order", default_value_t = random_seed(), hide_default_value = true)]
+    seed: u64,
+
+    /// Write a structured log of all operations to this file
+    ///
+    /// Every operation and its outcome is appended as one JSON object per line, e.g.
+    ///     {"detail":null,"op":"add","outcome":"added","target":"rgf__ntc-stefan_battery-historian","time":"..."}
+    /// The output on stdout is not changed.
+    #[clap(long, verbatim_doc_comment)]
+    log_file: Option<PathBuf>,
+
+    /// Size in bytes after which the log file is rotated
+    ///
+    /// The full log is moved to `<log-file>.1`, older ones to `.2` and `.3`.
+    #[clap(long, default_value = "10485760")]
+    log_max_size: u64,
+
+    /// Compare two forks with each other
+    ///
+    /// Lists the commits on the default branch of each fork which the other fork does not have,
+    /// i.e. everything since their merge base. Both forks have to be part of the same fork
+    /// network. The compare api of github is used, no remotes are added. Commits with a signature
+    /// github could verify are marked with `(verified)`. Example:
+    ///
+    ///     $ rgf --compare-forks ntc-stefan/battery-historian ippocratis/battery-historian
+    ///     merge-base 29a8e0c
+    ///     ntc-stefan/battery-historian master | 1 unique
+    ///       1f2e3d4 (verified) Fix parsing of wakelocks
+    ///     ippocratis/battery-historian master | 0 unique
+    #[clap(long, num_args = 2, value_names = ["A", "B"], verbatim_doc_comment)]
+    compare_forks: Option<Vec<String>>,
+
+    /// Version of the github REST api to request
+    ///
+    /// Sent as `X-GitHub-Api-Version` header with every request, so a new default version on the
+    /// side of github does not change the responses rgf works with. The available versions are
+    /// listed at https://docs.github.com/rest/about-the-rest-api/api-versions.
+    #[clap(long, default_value = api::DEFAULT_API_VERSION)]
+    api_version: String,
+
+    /// Print the api calls the given options would make and exit without making them
+    ///
+    /// Helps to estimate the cost of a run on the rate limit. Calls whose number depends on the
+    /// responses are counted with their minimum and marked with `+`. Example:
+    ///
+    ///     $ rgf --org google --list --rate-limit --explain
+    ///     GET /rate_limit | 1
+    ///     GET /orgs/google/repos | 1+ (one per 30 repositories)
+    ///     GET /repos/google/<repo>/forks?page=1&per_page=10&sort=newest | 0+ (one per repository with forks)
+    ///     api calls: 2+
+    #[clap(long, default_value = "false", verbatim_doc_comment)]
+    explain: bool,
+
+    /// Number of retries all api requests of a run may use together
+    ///
+    /// Transient errors like timeouts and server errors are retried up to 3 times per request.
+    /// Once the budget is used up, failed requests are not retried anymore, which bounds the
+    /// runtime against a flaky api. The remaining budget is printed to stderr with `--verbose`.
+    /// Unlimited by default.
+    #[clap(long)]
+    retry_budget: Option<u32>,
+
+    /// Retry fetches and connections of git after transient network errors
+    ///
+    /// Applies to `--update` and `--verify-remotes`. Errors like a reset connection, a failed
+    /// name resolution or a timeout are retried with a doubling wait from 2s on, other errors
+    /// like a missing repository fail right away. Independent of the retries of api requests.
+    #[clap(long, default_value = "false")]
+    retry_network_errors: bool,
+
+    /// Retries of a single git network operation for `--retry-network-errors`
+    #[clap(long, default_value = "3", requires = "retry_network_errors")]
+    max_retries: u32,
+
+    /// Do not verify TLS certificates, INSECURE
+    ///
+    /// Accepts any certificate from the github api and from the git hosts in every git network
+    /// operation: the connection check of `--verify-remotes`, the fetches of `--update`, also of
+    /// remotes renamed with `--prune`, and the ref listing of `--update --dry-run-json`. Anyone on
+    /// the network path can then read the token, forge responses and serve other commits, so it
+    /// is only meant for hosts with a self-signed certificate on a trusted network. A warning is
+    /// printed on every run with it.
+    #[clap(long, default_value = "false")]
+    no_verify_ssl: bool,
+
+    /// Github token for authentication
+    #[clap(short, long, env="GITHUB_TOKEN")]
+    token: Option<String>,
+
+    /// Print version, as `{"name":...,"version":...}` with `--format json`
+    #[clap(short = 'V', long)]
+    version: bool,
+
+    /// Print the completion script for this shell
+    ///
+    /// The script completes the command as it was invoked, e.g. `rgf`. Example for bash:
+    ///
+    ///     $ rgf --completions bash > ~/.local/share/bash-completion/completions/rgf
+    #[clap(long, value_name = "SHELL", verbatim_doc_comment)]
+    completions: Option<clap_complete::Shell>,
+
+    /// Write the completion script for this shell to where the shell looks for it
+    ///
+    /// That is `~/.local/share/bash-completion/completions/` for bash, `~/.config/fish/completions/`
+    /// for fish (both following `XDG_DATA_HOME` and `XDG_CONFIG_HOME`) and `~/.zfunc/` for zsh.
+    /// Missing directories are created. Other shells need `--completions-install-dir`. Example:
+    ///
+    ///     $ rgf --completions-install bash
+    ///     Installed the bash completions to /home/silvio/.local/share/bash-completion/completions/rgf
+    #[clap(long, value_name = "SHELL", conflicts_with = "completions", verbatim_doc_comment)]
+    completions_install: Option<clap_complete::Shell>,
+
+    /// Directory `--completions-install` writes the script to instead
+    #[clap(long, value_name = "DIR", requires = "completions_install")]
+    completions_install_dir: Option<PathBuf>,
+
+    // Arguments
+
+    /// The repository from which the forks are to be fetched
+    ///
+    /// With `-` a list of repositories is read from stdin, one `<owner>/<repo>` per line. The
+    /// output is grouped by repository like with `--org`. Invalid lines and repositories which can
+    /// not be listed are reported on stderr, the remaining ones are still processed. Example:
+    ///
+    ///     $ printf 'google/battery-historian\ngoogle/guava\n' | rgf - --list
+    ///     # google/battery-historian
+    ///     ntc-stefan/battery-historian | 0
+    ///     ...
+    #[clap(verbatim_doc_comment)]
+    #[clap(required_unless_present_any = ["org", "verify_remotes", "follow_renamed_forks", "compare_forks", "dedup_by_content", "list_remotes", "update", "doctor", "merge_preview", "migrate_prefix", "version", "completions", "completions_install"])]
+    repository: Option<String>,
+}
+
+/// Which side wins in `--sync`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum SourceOfTruth {
+    /// The forks on github, rgf remotes of no fetched fork are removed
+    Api,
+    /// The remotes, missing ones are added but none is removed
+    Git,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Format {
+    /// One line per entry with `|` separated fields
+    Text,
+    /// A JSON array of objects
+    Json,
+}
+
+/// A fork as printed by `--list --format json`, or its source with `--include-source`.
+#[derive(Serialize, Debug)]
+struct ForkRecord<'a> {
+    role: &'a str,
+    source: &'a str,
+    full_name: &'a str,
+    owner_type: Option<&'a str>,
+    clone_url: &'a str,
+    html_url: &'a str,
+    forks_count: i64,
+    stargazers_count: i64,
+    size: i64,
+    created_at: Option<String>,
+    pushed_at: Option<String>,
+    contributors: Option<u64>,
+    last_commit: Option<&'a LastCommit>,
+    upstream_prs: Option<u64>,
+    topics: Option<&'a Topics>,
+    relationship: Option<&'a Relationship>,
+    divergence: Option<&'a Divergence>,
+}
+
+impl<'a> ForkRecord<'a> {
+    fn new(source: &'a str, fork: &'a MinimalRepository, contributors: Option<u64>, last_commit: Option<&'a LastCommit>, upstream_prs: Option<u64>, topics: Option<&'a Topics>, relationship: Option<&'a Relationship>) -> ForkRecord<'a> {
+        ForkRecord {
+            role: "fork",
+            source,
+            full_name: &fork.full_name,
+            owner_type: fork.owner.as_ref().map(|o| o.type_.as_str()),
+            clone_url: &fork.clone_url,
+            html_url: &fork.html_url,
+            forks_count: fork.forks_count,
+            stargazers_count: fork.stargazers_count,
+            size: fork.size,
+            created_at: fork.created_at.map(|t| t.to_rfc3339()),
+            pushed_at: fork.pushed_at.map(|t| t.to_rfc3339()),
+            contributors,
+            last_commit,
+            upstream_prs,
+            topics,
+            relationship,
+            divergence: None,
+        }
+    }
+
+    /// Record of the source repository itself, see `--include-source`.
+    fn source(repo: &'a FullRepository) -> ForkRecord<'a> {
+        ForkRecord {
+            role: "source",
+            source: &repo.full_name,
+            full_name: &repo.full_name,
+            owner_type: repo.owner.as_ref().map(|o| o.type_.as_str()),
+            clone_url: &repo.clone_url,
+            html_url: &repo.html_url,
+            forks_count: repo.forks_count,
+            stargazers_count: repo.stargazers_count,
+            size: repo.size,
+            created_at: repo.created_at.map(|t| t.to_rfc3339()),
+            pushed_at: repo.pushed_at.map(|t| t.to_rfc3339()),
+            contributors: None,
+            last_commit: None,
+            upstream_prs: None,
+            topics: None,
+            relationship: None,
+            divergence: None,
+        }
+    }
+}
+
+/// An rgf remote as printed by `--list-remotes --format json`.
+#[derive(Serialize, Debug)]
+struct RemoteRecord {
+    name: String,
+    /// Only with `--strip-prefix-on-list`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    full_name: Option<String>,
+    url: String,
+    /// Only with `--verbose`, see `--store-description`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ForkSort {
+    /// Most recently created forks first
+    Newest,
+    /// Oldest forks first
+    Oldest,
+    /// Most starred forks first
+    Stargazers,
+    /// Most watched forks first
+    Watchers,
+    /// By `<owner>/<repo>`, sorted by rgf as github can not
+    Name,
+    /// Most forked forks first, sorted by rgf as github can not
+    Forks,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum SortDir {
+    Asc,
+    Desc,
+}
+
+impl ForkSort {
+    /// Direction of the key if `--sort-dir` is not given.
+    fn default_dir(&self) -> SortDir {
+        match self {
+            ForkSort::Newest | ForkSort::Stargazers | ForkSort::Watchers | ForkSort::Forks => SortDir::Desc,
+            ForkSort::Oldest | ForkSort::Name => SortDir::Asc,
+        }
+    }
+
+    /// Sorts `forks` like github does, but in direction `dir`. Ties are broken by name.
+    fn sort_stable(&self, forks: &mut [MinimalRepository], dir: SortDir) {
+        forks.sort_by(|a, b| {
+            let key = match self {
+                ForkSort::Newest | ForkSort::Oldest => a.created_at.cmp(&b.created_at),
+                ForkSort::Stargazers => a.stargazers_count.cmp(&b.stargazers_count),
+                ForkSort::Watchers => a.watchers_count.cmp(&b.watchers_count),
+                ForkSort::Name => a.full_name.to_lowercase().cmp(&b.full_name.to_lowercase()),
+                ForkSort::Forks => a.forks_count.cmp(&b.forks_count),
+            };
+            let key = if dir == SortDir::Desc { key.reverse() } else { key };
+            key.then_with(|| a.full_name.cmp(&b.full_name))
+        });
+    }
+
+    fn github_sort(&self) -> ReposListForksSort {
+        match self {
+            // Any order will do, all fetched forks are sorted afterwards
+            ForkSort::Newest | ForkSort::Name | ForkSort::Forks => ReposListForksSort::Newest,
+            ForkSort::Oldest => ReposListForksSort::Oldest,
+            ForkSort::Stargazers => ReposListForksSort::Stargazers,
+            ForkSort::Watchers => ReposListForksSort::Watchers,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Column {
+    Name,
+    Forks,
+    Stars,
+    Watchers,
+    #[value(name = "open_issues")]
+    OpenIssues,
+    Size,
+    Language,
+    License,
+    #[value(name = "created_at")]
+    CreatedAt,
+    #[value(name = "pushed_at")]
+    PushedAt,
+    Contributors,
+    #[value(name = "last_commit")]
+    LastCommit,
+    #[value(name = "upstream_prs")]
+    UpstreamPrs,
+    Topics,
+    Relationship,
+}
+
+/// Values of a listed repository for all `Column`s.
+struct Cells {
+    name: String,
+    forks: i64,
+    stars: i64,
+    watchers: i64,
+    open_issues: i64,
+    size: i64,
+    language: String,
+    license: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    pushed_at: Option<DateTime<Utc>>,
+    contributors: Option<u64>,
+    last_commit: Option<String>,
+    upstream_prs: Option<u64>,
+    topics: Option<String>,
+    relationship: Option<String>,
+}
+
+impl Cells {
+    fn fork(fork: &MinimalRepository, contributors: Option<u64>, last_commit: Option<&LastCommit>, upstream_prs: Option<u64>, topics: Option<&Topics>, relationship: Option<&Relationship>) -> Cells {
+        Cells {
+            name: fork.full_name.clone(),
+            forks: fork.forks_count,
+            stars: fork.stargazers_count,
+            watchers: fork.watchers_count,
+            open_issues: fork.open_issues_count,
+            size: fork.size,
+            language: fork.language.clone(),
+            license: fork.license.as_ref().map(|l| l.spdx_id.clone()),
+            created_at: fork.created_at,
+            pushed_at: fork.pushed_at,
+            contributors,
+            last_commit: last_commit.map(|commit| commit.to_string()),
+            upstream_prs,
+            topics: topics.map(|topics| topics.to_string()),
+            relationship: relationship.map(|relationship| relationship.to_string()),
+        }
+    }
+
+    /// Cells of the source repository, see `--include-source`.
+    fn source(repo: &FullRepository) -> Cells {
+        Cells {
+            name: repo.full_name.clone(),
+            forks: repo.forks_count,
+            stars: repo.stargazers_count,
+            watchers: repo.watchers_count,
+            open_issues: repo.open_issues_count,
+            size: repo.size,
+            language: repo.language.clone(),
+            license: repo.license.as_ref().map(|l| l.spdx_id.clone()),
+            created_at: repo.created_at,
+            pushed_at: repo.pushed_at,
+            contributors: None,
+            last_commit: None,
+            upstream_prs: None,
+            topics: Some(Topics(repo.topics.clone()).to_string()),
+            relationship: Some(Relationship::of(repo).to_string()),
+        }
+    }
+
+    fn get(&self, column: Column, args: &Args) -> String {
+        let or_unknown = |value: Option<String>| value.filter(|v| !v.is_empty()).unwrap_or("?".to_string());
+        match column {
+            Column::Name => abbrev(&self.name, args),
+            Column::Forks => self.forks.to_string(),
+            Column::Stars => self.stars.to_string(),
+            Column::Watchers => self.watchers.to_string(),
+            Column::OpenIssues => self.open_issues.to_string(),
+            Column::Size => self.size.to_string(),
+            Column::Language => or_unknown(Some(self.language.clone())),
+            Column::License => or_unknown(self.license.clone()),
+            Column::CreatedAt => or_unknown(self.created_at.map(|t| t.to_rfc3339())),
+            Column::PushedAt => or_unknown(self.pushed_at.map(|t| t.to_rfc3339())),
+            Column::Contributors => or_unknown(self.contributors.map(|count| count.to_string())),
+            Column::LastCommit => or_unknown(self.last_commit.clone()),
+            Column::UpstreamPrs => or_unknown(self.upstream_prs.map(|count| count.to_string())),
+            Column::Topics => self.topics.clone().unwrap_or("?".to_string()),
+            Column::Relationship => self.relationship.clone().unwrap_or("?".to_string()),
+        }
+    }
+}
+
+/// Lines of `rows`, the cells separated by ` | ` and padded to the same width if `aligned`.
+fn render_rows(rows: &[Vec<String>], aligned: bool) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in rows.iter().filter(|_| aligned) {
+        for (i, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(i) {
+                Some(max) => *max = (*max).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let last = row.len().saturating_sub(1);
+            row.iter()
+                .enumerate()
+                // The last column is not padded, no trailing blanks
+                .map(|(i, cell)| match widths.get(i) {
+                    Some(width) if i < last => format!("{}{}", cell, " ".repeat(width - cell.chars().count())),
+                    _ => cell.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(" | ")
+        })
+        .collect()
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum CountBy {
+    /// Main language as detected by github
+    Language,
+    /// `User` or `Organization`
+    OwnerType,
+    /// SPDX id of the license, e.g. `MIT`
+    License,
+}
+
+impl CountBy {
+    /// Bucket `fork` falls into, `None` if it lacks the field.
+    fn bucket<'a>(&self, fork: &'a MinimalRepository) -> Option<&'a str> {
+        let bucket = match self {
+            CountBy::Language => Some(fork.language.as_str()),
+            CountBy::OwnerType => fork.owner.as_ref().map(|o| o.type_.as_str()),
+            CountBy::License => fork.license.as_ref().map(|l| if l.spdx_id.is_empty() { l.key.as_str() } else { l.spdx_id.as_str() }),
+        };
+        bucket.filter(|b| !b.is_empty())
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OwnerType {
+    /// Forks of personal accounts
+    User,
+    /// Forks of organizations
+    Org,
+}
+
+impl OwnerType {
+    /// The `type` github reports for owners of this kind.
+    fn github_type(&self) -> &'static str {
+        match self {
+            OwnerType::User => "User",
+            OwnerType::Org => "Organization",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OwnerRepo {
+    pub owner: String,
+    pub repo: String,
+}
+
+impl OwnerRepo {
+    pub fn new(orinput: &str) -> Result<OwnerRepo, String> {
+        let parts: Vec<&str> = orinput.split('/').collect();
+        if parts.len() != 2 {
+            return Err("Invalid repository format".to_string());
+        }
+        Ok(OwnerRepo {
+            owner: parts[0].to_string(),
+            repo: parts[1].to_string(),
+        })
+    }
+}
+
+
+/// Parses durations like `90s`, `30m`, `2h`, `1d` or `2w`.
+fn parse_duration(input: &str) -> Result<Duration, String> {
+    let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: i64 = number.parse().map_err(|_| format!("Invalid duration '{}', expected e.g. 2h or 7d", input))?;
+    match unit {
+        "s" => Ok(Duration::seconds(number)),
+        "m" => Ok(Duration::minutes(number)),
+        "h" => Ok(Duration::hours(number)),
+        "d" => Ok(Duration::days(number)),
+        "w" => Ok(Duration::weeks(number)),
+        _ => Err(format!("Invalid duration unit '{}', expected one of s, m, h, d, w", unit)),
+    }
+}
+
+/// Parses a tier of `--activity-tiers`, keeping the input as its label.
+fn parse_tier(input: &str) -> Result<(String, Duration), String> {
+    Ok((input.to_string(), parse_duration(input)?))
+}
+
+/// What the forks are compared with, see `--compare-base`.
+#[derive(Clone, Debug)]
+enum CompareBase {
+    /// A branch or tag of the source
+    Ref(String),
+    /// A full commit SHA
+    Commit(String),
+}
+
+/// Parses `--compare-base`, a full commit SHA or else a branch or tag name.
+fn parse_compare_base(input: &str) -> Result<CompareBase, String> {
+    if !input.is_empty() && input.chars().all(|c| c.is_ascii_hexdigit()) {
+        // SHA-1 or SHA-256
+        if input.len() == 40 || input.len() == 64 {
+            return Ok(CompareBase::Commit(input.to_lowercase()));
+        }
+        if input.len() >= 7 {
+            return Err(format!("'{}' looks like an abbreviated commit, give the full SHA", input));
+        }
+    }
+    if !git2::Reference::is_valid_name(&format!("refs/heads/{}", input)) {
+        return Err(format!("'{}' is no valid branch or tag name", input));
+    }
+    Ok(CompareBase::Ref(input.to_string()))
+}
+
+/// Checks that `input` is a refspec like `+refs/heads/*:refs/remotes/{name}/*`.
+fn parse_refspec(input: &str) -> Result<String, String> {
+    let invalid = |reason: &str| Err(format!("Invalid refspec '{}': {}", input, reason));
+    let spec = input.strip_prefix('+').unwrap_or(input);
+    let Some((src, dst)) = spec.split_once(':') else { return invalid("expected <src>:<dst>") };
+    if src.matches('*').count() != dst.matches('*').count() || src.matches('*').count() > 1 {
+        return invalid("both sides need the same single '*' or none");
+    }
+    for side in [src, dst] {
+        // A pattern is a valid ref name once the placeholders are filled in
+        let name = side.replace('*', "x").replace("{name}", "x");
+        if !git2::Reference::is_valid_name(&name) {
+            return invalid(&format!("'{}' is no valid ref", side));
+        }
+    }
+    Ok(input.to_string())
+}
+
+/// Drops all forks not matching `query` and sorts the others by how well they match.
+fn search_forks(forks: &mut Vec<MinimalRepository>, query: &str) {
+    let matcher = SkimMatcherV2::default().smart_case();
+    let mut scored: Vec<(i64, MinimalRepository)> = forks.drain(..)
+        .filter_map(|fork| Some((matcher.fuzzy_match(&fork.full_name, query)?, fork)))
+        .collect();
+    // Stable, so equally good matches keep the order of github
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    forks.extend(scored.into_iter().map(|(_, fork)| fork));
+}
+
+/// Reads the lower case owner logins of `--owner-allowlist-file`.
+fn read_allowlist(path: &Path) -> Result<HashSet<String>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+/// Template of `--output-template-file`.
+fn read_template(path: &str) -> Result<template::Template, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let columns: Vec<String> = Column::value_variants().iter()
+        .filter_map(|column| column.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    let mut fork_fields: Vec<&str> = vec!["source", "url"];
+    fork_fields.extend(columns.iter().map(String::as_str));
+    template::parse(&content, &fork_fields, &["sources", "count"])
+}
+
+/// Lowercased names of the forks in a listing of `--format json`, see `--only-new-since`.
+fn read_snapshot(path: &Path) -> Result<HashSet<String>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let invalid = |reason: String| format!("{} is no listing of --list --format json: {}", path.display(), reason);
+    let json: serde_json::Value = serde_json::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+    let records = json.as_array().ok_or_else(|| invalid("not an array".to_string()))?;
+    records.iter()
+        .enumerate()
+        .map(|(index, record)| {
+            record.get("full_name")
+                .and_then(|name| name.as_str())
+                .map(str::to_lowercase)
+                .ok_or_else(|| invalid(format!("entry {} has no full_name", index + 1)))
+        })
+        .collect()
+}
+
+/// Drops all forks not matching the filter options.
+fn filter_forks(forks: &mut Vec<MinimalRepository>, args: &Args, allowlist: Option<&HashSet<String>>) {
+    let now = Utc::now();
+    forks.retain(|fork| {
+        if let Some(allowlist) = allowlist {
+            let owner = fork.full_name.split('/').next().unwrap_or_default();
+            if !allowlist.contains(&owner.to_lowercase()) {
+                return false;
+            }
+        }
+        if let Some(since) = args.created_since {
+            match fork.created_at {
+                Some(created_at) if created_at >= now - since => (),
+                _ => return false,
+            }
+        }
+        if args.max_size.is_some_and(|max_size| fork.size > max_size) {
+            return false;
+        }
+        if let Some(owner_type) = args.owner_type {
+            if fork.owner.as_ref().map(|o| o.type_.as_str()) != Some(owner_type.github_type()) {
+                return false;
+            }
+        }
+        if args.filter_expr.as_ref().is_some_and(|expr| !expr.matches(fork)) {
+            return false;
+        }
+        true
+    });
+}
+
+async fn list_forks(client: &Client, owner: &str, repo: &str, args: &Args) -> Result<Vec<MinimalRepository>, ClientError> {
+    let mut forks = Vec::new();
+    for page in args.page..args.page.saturating_add(args.max_pages) {
+        let response = client.repos().list_forks(owner, repo, args.sort.github_sort(), args.per_page as i64, page as i64).await?;
+        if response.status != StatusCode::OK {
+            panic!("Response Status not okay: {}", response.status);
+        }
+        // A page which is not full is the last one, github serves at most 100 per page
+        let last = response.body.len() < args.per_page.min(100) as usize;
+        forks.extend(response.body);
+        if last {
+            break;
+        }
+    }
+    Ok(forks)
+}
+
+/// Prints the api error `e` a run stops with, with a hint if the token is likely the cause.
+fn api_error(e: &ClientError, args: &Args) {
+    println!("Error: {}", e);
+    if let Some(hint) = api::token_hint(e, args.token.as_deref()) {
+        println!("Hint: {}", hint);
+    }
+}
+
+/// Collects the forks of all source repositories of an organization, grouped by repository.
+///
+/// Hitting the rate limit stops the collection, the forks gathered so far are still returned.
+///
+/// The flag is false if the listing broke off before all repositories were listed.
+async fn list_org_forks(client: &Client, org: &str, args: &Args, log: &mut OpLog) -> (Vec<(String, Vec<MinimalRepository>)>, bool) {
+    let repos = match client.repos().list_all_for_org(org, ReposListOrgType::Sources, ReposListOrgSort::FullName, Order::Asc).await {
+        Ok(response) => response.body,
+        Err(e) => {
+            log.log("list-repos", org, "failed", Some(&e.to_string()));
+            api_error(&e, args);
+            exit(1);
+        }
+    };
+
+    let mut out = Vec::new();
+    for repo in repos {
+        // Spare the api call, nothing to list anyway
+        if repo.forks_count == 0 {
+            continue;
+        }
+
+        match list_forks(client, org, &repo.name, args).await {
+            Ok(forks) => out.push((repo.full_name, forks)),
+            Err(ClientError::RateLimited { duration }) => {
+                log.log("list-forks", &repo.full_name, "rate-limited", Some(&format!("{} seconds", duration)));
+                println!("Rate limited for the next {} seconds, skipping remaining repositories of {}", duration, org);
+                return (out, false);
+            },
+            Err(e) => {
+                log.log("list-forks", &repo.full_name, "failed", Some(&e.to_string()));
+                api_error(&e, args);
+                exit(1);
+            }
+        }
+    }
+    (out, true)
+}
+
+/// Page number of the `rel="last"` link of a `Link` header.
+fn last_page(link: &str) -> Option<u64> {
+    link.split(',')
+        .find(|l| l.contains("rel=\"last\""))
+        .and_then(|l| l.split(['?', '&', '>']).find_map(|param| param.strip_prefix("page=")))
+        .and_then(|page| page.parse().ok())
+}
+
+/// Number of contributors of a repository, without listing them.
+///
+/// With one contributor per page, the number of the last page is the number of contributors.
+async fn contributor_count(client: &Client, owner: &str, repo: &str) -> Result<u64, ClientError> {
+    let response = match client.repos().list_contributors(owner, repo, "", 1, 1).await {
+        Ok(response) => response,
+        // Empty repositories answer 204 without a body, which octorust fails to parse
+        Err(ClientError::SerdeJsonError(_)) => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let last = response.headers.get(reqwest::header::LINK).and_then(|link| link.to_str().ok()).and_then(last_page);
+    // Without a link everything fit on the first page
+    Ok(last.unwrap_or(response.body.len() as u64))
+}
+
+/// Outcome of an operation on a single fork of many.
+enum ForkOutcome<T> {
+    Done(T),
+    /// Not attempted, e.g. after hitting the rate limit
+    Skipped,
+    /// Took longer than `--timeout-per-fork`
+    TimedOut,
+}
+
+/// Runs `operation` for a single fork, giving up after `timeout`.
+async fn per_fork<T>(timeout: Option<std::time::Duration>, operation: impl Future<Output = T>) -> ForkOutcome<T> {
+    match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, operation).await {
+            Ok(result) => ForkOutcome::Done(result),
+            Err(_) => ForkOutcome::TimedOut,
+        },
+        None => ForkOutcome::Done(operation.await),
+    }
+}
+
+impl Args {
+    /// Whether the contributors of the forks are needed.
+    fn with_contributors(&self) -> bool {
+        self.contributors || self.min_contributors.is_some() || self.has_column(Column::Contributors)
+    }
+
+    /// Whether the last commits of the forks are needed.
+    fn with_last_commit(&self) -> bool {
+        self.last_commit || self.has_column(Column::LastCommit)
+    }
+
+    /// Whether text columns are aligned, see `--aligned`.
+    fn align(&self) -> bool {
+        (self.aligned || self.columns.is_some()) && stdout().is_terminal()
+    }
+
+    /// Whether the pull requests of the fork owners against the sources are needed.
+    fn with_upstream_prs(&self) -> bool {
+        self.upstream_prs || self.min_upstream_prs.is_some() || self.has_column(Column::UpstreamPrs)
+    }
+
+    /// Whether the topics of the forks are needed.
+    fn with_topics(&self) -> bool {
+        self.topics || self.highlight_shared_topics || self.has_column(Column::Topics)
+    }
+
+    /// Whether the forks are compared with their sources.
+    fn with_divergences(&self) -> bool {
+        self.add_if_ahead.is_some() || self.skip_forks_behind || self.class.is_some() || self.only_verified || self.compare
+    }
+
+    /// Whether the parents and sources of the forks are needed.
+    fn with_relationships(&self) -> bool {
+        self.relationships || self.has_column(Column::Relationship)
+    }
+
+    fn has_column(&self, column: Column) -> bool {
+        let name = column.to_possible_value().expect("No column is skipped");
+        self.columns.as_ref().is_some_and(|columns| columns.contains(&column))
+            || self.output_template_file.as_ref().is_some_and(|template| template.fields().any(|field| field == name.get_name()))
+    }
+}
+
+/// Shuffles `items` with `seed`, the same seed gives the same order.
+fn shuffle<T>(items: &mut [T], seed: u64) {
+    // splitmix64, good enough to spread load and needs no dependency
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    };
+    for i in (1..items.len()).rev() {
+        items.swap(i, (next() % (i as u64 + 1)) as usize);
+    }
+}
+
+/// Seed of `--randomize-order` without `--seed`, from the clock.
+fn random_seed() -> u64 {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    now.as_nanos() as u64
+}
+
+/// Puts `items` into the processing order of `--randomize-order`.
+fn processing_order<T>(items: &mut [T], args: &Args) {
+    if args.randomize_order {
+        shuffle(items, args.seed);
+    }
+}
+
+/// Gets a value per fork of `sources` with `get`, `--jobs` forks at a time.
+///
+/// With `--batch-size` the forks are processed in batches, with a pause between them.
+///
+/// `get` is called with the source and the fork. `what` names the value in the messages, `op`
+/// in the log. Hitting the rate limit stops the fetching, forks without a value are missing in
+/// the result. So are the ones which failed or timed out.
+async fn fork_values<T, F, Fut>(sources: &[(String, Vec<MinimalRepository>)], args: &Args, log: &mut OpLog, op: &str, what: &str, get: F) -> HashMap<String, T>
+where
+    T: fmt::Display + Send + 'static,
+    F: Fn(&str, &MinimalRepository) -> Fut,
+    Fut: Future<Output = Result<T, ClientError>> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(args.jobs.max(1)));
+    let rate_limited = Arc::new(AtomicBool::new(false));
+    let timeout = args.timeout_per_fork.and_then(|t| t.to_std().ok());
+    let mut forks: Vec<_> = sources.iter().flat_map(|(source, forks)| forks.iter().map(move |fork| (source, fork))).collect();
+    processing_order(&mut forks, args);
+
+    let batch_size = args.batch_size.map_or(forks.len(), |size| size as usize).max(1);
+    let batch_delay = args.batch_delay.to_std().unwrap_or_default();
+    let mut outcomes = Vec::new();
+    for (batch, forks) in forks.chunks(batch_size).enumerate() {
+        // Waiting is pointless if all remaining forks are skipped anyway
+        if batch > 0 && !rate_limited.load(Ordering::Relaxed) {
+            tokio::time::sleep(batch_delay).await;
+        }
+        let mut tasks = JoinSet::new();
+        for (source, fork) in forks {
+            let semaphore = Arc::clone(&semaphore);
+            let rate_limited = Arc::clone(&rate_limited);
+            let name = fork.full_name.clone();
+            let getting = get(source, fork);
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.unwrap();
+                if rate_limited.load(Ordering::Relaxed) {
+                    return (name, ForkOutcome::Skipped);
+                }
+                let outcome = per_fork(timeout, getting).await;
+                if let ForkOutcome::Done(Err(ClientError::RateLimited { .. })) = outcome {
+                    rate_limited.store(true, Ordering::Relaxed);
+                }
+                (name, outcome)
+            });
+        }
+        while let Some(joined) = tasks.join_next().await {
+            outcomes.push(joined.expect("Fork task failed"));
+        }
+    }
+
+    let mut values = HashMap::new();
+    let mut skipped = 0;
+    let mut timed_out = Vec::new();
+    for (name, outcome) in outcomes {
+        match outcome {
+            ForkOutcome::Done(Ok(value)) => {
+                log.log(op, &name, "ok", Some(&value.to_string()));
+                values.insert(name, value);
+            },
+            ForkOutcome::Done(Err(ClientError::RateLimited { duration })) => {
+                log.log(op, &name, "rate-limited", Some(&format!("{} seconds", duration)));
+                skipped += 1;
+            },
+            ForkOutcome::Done(Err(e)) => {
+                log.log(op, &name, "failed", Some(&e.to_string()));
+                eprintln!("Failed to get the {} of {}: {}", what, name, e);
+            },
+            ForkOutcome::Skipped => skipped += 1,
+            ForkOutcome::TimedOut => {
+                log.log(op, &name, "timed-out", None);
+                timed_out.push(name);
+            },
+        }
+    }
+    if skipped > 0 {
+        eprintln!("Rate limited, {} of {} forks not fetched", what, skipped);
+    }
+    if !timed_out.is_empty() {
+        timed_out.sort();
+        eprintln!("Timed out getting the {} of {} forks: {}", what, timed_out.len(), timed_out.join(" "));
+    }
+    values
+}
+
+/// Newest commit on the default branch of a fork, see `--last-commit`.
+#[derive(Serialize, Debug)]
+struct LastCommit {
+    sha: String,
+    author: String,
+    date: String,
+}
+
+impl fmt::Display for LastCommit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.author, self.date)
+    }
+}
+
+/// Topics of a fork, see `--topics`.
+#[derive(Serialize, Debug)]
+struct Topics(Vec<String>);
+
+impl fmt::Display for Topics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "-");
+        }
+        write!(f, "{}", self.0.join(","))
+    }
+}
+
+/// Parent and source of a fork, see `--relationships`.
+#[derive(Serialize, Debug)]
+struct Relationship {
+    parent: Option<String>,
+    source: Option<String>,
+}
+
+impl Relationship {
+    fn of(repo: &FullRepository) -> Relationship {
+        Relationship {
+            parent: repo.parent.as_ref().map(|parent| parent.full_name.clone()),
+            source: repo.source.as_ref().map(|source| source.full_name.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Relationship {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.parent, &self.source) {
+            (Some(parent), Some(source)) if parent != source => write!(f, "{} (source {})", parent, source),
+            (Some(parent), _) => write!(f, "{}", parent),
+            (None, _) => write!(f, "-"),
+        }
+    }
+}
+
+/// Full repository of a fork, see `--enrich`.
+struct Enriched(FullRepository);
+
+impl fmt::Display for Enriched {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.full_name)
+    }
+}
+
+/// Fills the empty fields of the forks in `sources` from their full repository, see `--enrich`.
+///
+/// Returns the relationships of the looked up forks, so `--relationships` needs no more calls.
+async fn enrich_forks(client: &Client, sources: &mut [(String, Vec<MinimalRepository>)], args: &Args, log: &mut OpLog) -> HashMap<String, Relationship> {
+    // Forks of several sources may be the same, each is looked up once
+    let mut unique: Vec<MinimalRepository> = Vec::new();
+    let mut seen = HashSet::new();
+    for fork in sources.iter().flat_map(|(_, forks)| forks) {
+        if seen.insert(fork.full_name.clone()) {
+            unique.push(fork.clone());
+        }
+    }
+    let full = fork_values(&[(String::new(), unique)], args, log, "enrich", "repository", |_, fork| {
+        let client = client.clone();
+        let name = fork.full_name.clone();
+        async move {
+            let (owner, repo) = name.split_once('/').unwrap_or((&name, ""));
+            Ok(Enriched(client.repos().get(owner, repo).await?.body))
+        }
+    })
+    .await;
+
+    for fork in sources.iter_mut().flat_map(|(_, forks)| forks.iter_mut()) {
+        let Some(Enriched(repo)) = full.get(&fork.full_name) else { continue };
+        let fill = |field: &mut String, value: &str| {
+            if field.is_empty() {
+                *field = value.to_string();
+            }
+        };
+        fill(&mut fork.language, &repo.language);
+        fill(&mut fork.description, &repo.description);
+        fill(&mut fork.homepage, &repo.homepage);
+        fill(&mut fork.default_branch, &repo.default_branch);
+        if fork.license.is_none() {
+            fork.license = repo.license.as_ref().map(|l| License {
+                key: l.key.clone(),
+                name: l.name.clone(),
+                node_id: l.node_id.clone(),
+                spdx_id: l.spdx_id.clone(),
+                url: l.url.clone(),
+            });
+        }
+        fork.created_at = fork.created_at.or(repo.created_at);
+        fork.pushed_at = fork.pushed_at.or(repo.pushed_at);
+    }
+    full.iter().map(|(name, Enriched(repo))| (name.clone(), Relationship::of(repo))).collect()
+}
+
+/// Pull requests the owner of every fork in `sources` opened against the source, see `fork_values`.
+async fn upstream_pr_counts(client: &Client, sources: &[(String, Vec<MinimalRepository>)], args: &Args, log: &mut OpLog) -> HashMap<String, u64> {
+    fork_values(sources, args, log, "upstream-prs", "upstream pull requests", |source, fork| {
+        let client = client.clone();
+        let owner = fork.full_name.split('/').next().unwrap_or_default();
+        let query = format!("is:pr repo:{} author:{}", source, owner);
+        async move {
+            // Only the total is needed, not the pull requests themselves
+            let response = client.search().issues_and_pull_requests(&query, SearchIssuesPullRequestsSort::Noop, Order::Noop, 1, 1).await?;
+            Ok(response.body.total_count.max(0) as u64)
+        }
+    })
+    .await
+}
+
+/// Topics of all forks in `sources`, see `fork_values`.
+async fn fork_topics(client: &Client, sources: &[(String, Vec<MinimalRepository>)], args: &Args, log: &mut OpLog) -> HashMap<String, Topics> {
+    fork_values(sources, args, log, "topics", "topics", |_, fork| {
+        let client = client.clone();
+        let name = fork.full_name.clone();
+        async move {
+            let (owner, repo) = name.split_once('/').unwrap_or((&name, ""));
+            // github allows at most 20 topics per repository, one page holds them all
+            let topics = client.repos().get_all_topics(owner, repo, 1, 100).await?.body;
+            Ok(Topics(topics.names))
+        }
+    })
+    .await
+}
+
+/// Parents and sources of all forks in `sources`, see `fork_values`.
+async fn fork_relationships(client: &Client, sources: &[(String, Vec<MinimalRepository>)], args: &Args, log: &mut OpLog) -> HashMap<String, Relationship> {
+    fork_values(sources, args, log, "relationship", "parent and source", |_, fork| {
+        let client = client.clone();
+        let name = fork.full_name.clone();
+        async move {
+            let (owner, repo) = name.split_once('/').unwrap_or((&name, ""));
+            Ok(Relationship::of(&client.repos().get(owner, repo).await?.body))
+        }
+    })
+    .await
+}
+
+/// Last commits of all forks in `sources`, see `fork_values`.
+async fn last_commits(client: &Client, sources: &[(String, Vec<MinimalRepository>)], args: &Args, log: &mut OpLog) -> HashMap<String, LastCommit> {
+    fork_values(sources, args, log, "last-commit", "last commit", |_, fork| {
+        let client = client.clone();
+        let name = fork.full_name.clone();
+        async move {
+            let (owner, repo) = name.split_once('/').unwrap_or((&name, ""));
+            // HEAD is the default branch
+            let commit = client.repos().get_commit(owner, repo, 0, 0, "HEAD").await?.body;
+            let author = commit.commit.author.as_ref();
+            Ok(LastCommit {
+                author: author.map(|a| a.name.clone())
+                    .filter(|name| !name.is_empty())
+                    .or(commit.author.map(|a| a.login))
+                    .unwrap_or("?".to_string()),
+                date: author.map(|a| a.date.clone()).unwrap_or_default(),
+                sha: commit.sha,
+            })
+        }
+    })
+    .await
+}
+
+/// Counts the contributors of all forks in `sources`, see `fork_values`.
+async fn contributor_counts(client: &Client, sources: &[(String, Vec<MinimalRepository>)], args: &Args, log: &mut OpLog) -> HashMap<String, u64> {
+    fork_values(sources, args, log, "contributors", "contributors", |_, fork| {
+        let client = client.clone();
+        let name = fork.full_name.clone();
+        async move {
+            // Full names of github are always <owner>/<repo>
+            let (owner, repo) = name.split_once('/').unwrap_or((&name, ""));
+            contributor_count(&client, owner, repo).await
+        }
+    })
+    .await
+}
+
+/// How the commits of a fork relate to those of its source, see `--class`.
+#[derive(ValueEnum, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ForkClass {
+    /// Only commits the source does not have
+    Ahead,
+    /// Only misses commits of the source
+    Behind,
+    /// Both
+    Diverged,
+}
+
+impl fmt::Display for ForkClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_possible_value().expect("No variant is skipped").get_name())
+    }
+}
+
+/// A commit of a fork its source does not have, see `--only-verified` and `--compare`.
+#[derive(Serialize, Debug)]
+struct UniqueCommit {
+    sha: String,
+    /// First line of the commit message
+    subject: String,
+    verified: bool,
+}
+
+/// A fork compared with its source, see `divergences`.
+#[derive(Serialize, Debug)]
+struct Divergence {
+    ahead_by: u64,
+    behind_by: u64,
+    /// `None` if the fork is identical to its source
+    class: Option<ForkClass>,
+    /// At most 250, the compare api lists no more
+    commits: Vec<UniqueCommit>,
+}
+
+impl Divergence {
+    /// Whether github verified the signatures of all unique commits.
+    fn verified(&self) -> bool {
+        self.commits.len() as u64 >= self.ahead_by && self.commits.iter().all(|commit| commit.verified)
+    }
+
+    /// `+N/-M` with the commits ahead in green and those behind in red, see `--compare`.
+    fn summary(&self, palette: &Palette) -> String {
+        let ahead = palette.paint(palette.added, &format!("+{}", self.ahead_by));
+        let behind = palette.paint(palette.removed, &format!("-{}", self.behind_by));
+        format!("{}/{}", ahead, behind)
+    }
+
+    /// The newest `max` unique commits, newest first. The compare api lists the oldest first.
+    fn newest_commits(&self, max: usize) -> impl Iterator<Item = &UniqueCommit> {
+        self.commits.iter().rev().take(max)
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.class {
+            Some(class) => write!(f, "{} ahead, {}", self.ahead_by, class),
+            None => write!(f, "{} ahead, identical", self.ahead_by),
+        }
+    }
+}
+
+/// Compares each fork in `sources` with the default branch of its source.
+///
+/// `upstreams` are the source repositories, forks of a missing one are left out.
+async fn divergences(client: &Client, sources: &[(String, Vec<MinimalRepository>)], upstreams: &HashMap<String, FullRepository>, args: &Args, log: &mut OpLog) -> HashMap<String, Divergence> {
+    let mut known: Vec<(String, Vec<MinimalRepository>)> = sources.iter().filter(|(source, _)| upstreams.contains_key(source)).cloned().collect();
+    if let Some(CompareBase::Commit(sha)) = &args.compare_base {
+        let mut checked = Vec::new();
+        for (source, forks) in known {
+            match source_has_commit(client, &upstreams[&source], sha).await {
+                Ok(true) => checked.push((source, forks)),
+                Ok(false) => {
+                    log.log("compare-base", &source, "failed", Some(&format!("unknown commit {}", sha)));
+                    eprintln!("Error: {} has no commit {}, its forks are not compared", source, sha);
+                },
+                Err(e) => {
+                    log.log("compare-base", &source, "failed", Some(&e.to_string()));
+                    eprintln!("Error: Failed to look up commit {} of {}: {}", sha, source, e);
+                },
+            }
+        }
+        known = checked;
+    }
+    fork_values(&known, args, log, "ahead", "commits ahead", |source, fork| {
+        let client = client.clone();
+        let upstream = &upstreams[source];
+        // Full names of github are always <owner>/<repo>
+        let (owner, repo) = upstream.full_name.split_once('/').unwrap_or((&upstream.full_name, ""));
+        let repo = OwnerRepo { owner: owner.to_string(), repo: repo.to_string() };
+        let base = match &args.compare_base {
+            Some(CompareBase::Commit(sha)) => sha.clone(),
+            Some(CompareBase::Ref(name)) => format!("{}:{}", repo.owner, name),
+            None => format!("{}:{}", repo.owner, upstream.default_branch),
+        };
+        let head = format!("{}:{}", fork.full_name.split('/').next().unwrap_or_default(), fork.default_branch);
+        async move {
+            let comparison = unique_commits(&client, &repo, &base, &head).await?;
+            let class = match comparison.status {
+                CommitComparisonStatus::Ahead => Some(ForkClass::Ahead),
+                CommitComparisonStatus::Behind => Some(ForkClass::Behind),
+                CommitComparisonStatus::Diverged => Some(ForkClass::Diverged),
+                _ => None,
+            };
+            let commits = comparison.commits.iter()
+                .map(|commit| UniqueCommit {
+                    sha: commit.sha.clone(),
+                    subject: commit.commit.message.lines().next().unwrap_or_default().to_string(),
+                    verified: commit.commit.verification.as_ref().is_some_and(|v| v.verified),
+                })
+                .collect();
+            Ok(Divergence { ahead_by: comparison.ahead_by.max(0) as u64, behind_by: comparison.behind_by.max(0) as u64, class, commits })
+        }
+    })
+    .await
+}
+
+/// Whether `upstream` has the commit `sha`, github answers 422 for an unknown one.
+async fn source_has_commit(client: &Client, upstream: &FullRepository, sha: &str) -> Result<bool, ClientError> {
+    let (owner, repo) = upstream.full_name.split_once('/').unwrap_or((&upstream.full_name, ""));
+    match client.repos().get_commit(owner, repo, 0, 0, sha).await {
+        Ok(_) => Ok(true),
+        Err(ClientError::HttpError { status: StatusCode::NOT_FOUND | StatusCode::UNPROCESSABLE_ENTITY, .. }) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Collects the forks of all repositories listed on stdin, grouped by repository.
+///
+/// Invalid lines and failed listings are reported and skipped. Hitting the rate limit stops the
+/// collection, the forks gathered so far are still returned.
+///
+/// The flag is false if a line was skipped or the listing broke off.
+async fn list_stdin_forks(client: &Client, args: &Args, log: &mut OpLog) -> (Vec<(String, Vec<MinimalRepository>)>, bool) {
+    let mut out = Vec::new();
+    let mut complete = true;
+    for (number, line) in stdin().lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                println!("Error: Failed to read stdin: {}", e);
+                exit(1);
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let repo = match OwnerRepo::new(line) {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Error: line {}: {}: '{}'", number + 1, e, line);
+                complete = false;
+                continue;
+            }
+        };
+        match list_forks(client, &repo.owner, &repo.repo, args).await {
+            Ok(forks) => out.push((line.to_string(), forks)),
+            Err(ClientError::RateLimited { duration }) => {
+                log.log("list-forks", line, "rate-limited", Some(&format!("{} seconds", duration)));
+                eprintln!("Rate limited for the next {} seconds, skipping remaining repositories", duration);
+                return (out, false);
+            },
+            Err(e) => {
+                log.log("list-forks", line, "failed", Some(&e.to_string()));
+                eprintln!("Error: {}: {}", line, e);
+                if let Some(hint) = api::token_hint(&e, args.token.as_deref()) {
+                    eprintln!("Hint: {}", hint);
+                }
+                complete = false;
+            }
+        }
+    }
+    (out, complete)
+}
+
+/// The source repositories of `sources` with their stats, see `--include-source`.
+///
+/// Sources which can not be fetched are reported and left out.
+async fn source_repos(client: &Client, sources: &[(String, Vec<MinimalRepository>)], log: &mut OpLog) -> HashMap<String, FullRepository> {
+    let mut out = HashMap::new();
+    for (source, _) in sources {
+        let Ok(repo) = OwnerRepo::new(source) else { continue };
+        match client.repos().get(&repo.owner, &repo.repo).await {
+            Ok(response) => {
+                log.log("get-source", source, "ok", None);
+                out.insert(source.clone(), response.body);
+            },
+            Err(e) => {
+                log.log("get-source", source, "failed", Some(&e.to_string()));
+                eprintln!("Failed to get source repository {}: {}", source, e);
+            },
+        }
+    }
+    out
+}
+
+/// The root of the fork network of `owner_repo`, see `--root`.
+async fn root_repository(client: &Client, owner_repo: OwnerRepo, args: &Args, log: &mut OpLog) -> OwnerRepo {
+    let full_name = format!("{}/{}", owner_repo.owner, owner_repo.repo);
+    let repo = match client.repos().get(&owner_repo.owner, &owner_repo.repo).await {
+        Ok(response) => response.body,
+        Err(e) => {
+            log.log("get-root", &full_name, "failed", Some(&e.to_string()));
+            api_error(&e, args);
+            exit(1);
+        },
+    };
+    let Some(root) = repo.source.or(repo.parent) else {
+        log.log("get-root", &full_name, "no-fork", None);
+        eprintln!("{} is no fork, using its own forks", full_name);
+        return owner_repo;
+    };
+    log.log("get-root", &full_name, "ok", Some(&root.full_name));
+    if args.verbose {
+        eprintln!("Root of {}: {}", full_name, root.full_name);
+    }
+    match OwnerRepo::new(&root.full_name) {
+        Ok(root) => root,
+        Err(e) => {
+            println!("Error: Invalid root {} of {}: {}", root.full_name, full_name, e);
+            exit(1);
+        },
+    }
+}
+
+async fn default_branch(client: &Client, owner_repo: &OwnerRepo) -> Result<String, ClientError> {
+    let response = client.repos().get(&owner_repo.owner, &owner_repo.repo).await?;
+    Ok(response.body.default_branch)
+}
+
+/// Commits of `head` which are not part of `base`, both given as `<owner>:<branch>` of the same
+/// fork network.
+async fn unique_commits(client: &Client, repo: &OwnerRepo, base: &str, head: &str) -> Result<CommitComparison, ClientError> {
+    let response = client.repos().compare_commits(&repo.owner, &repo.repo, 0, 0, &format!("{}...{}", base, head)).await?;
+    if response.status != StatusCode::OK {
+        panic!("Response Status not okay: {}", response.status);
+    }
+    Ok(response.body)
+}
+
+/// Prints short sha and subject of `commits`. Signed commits github could verify are marked.
+fn print_commits(commits: &[CommitDataType], palette: &Palette) {
+    for commit in commits {
+        let subject = commit.commit.message.lines().next().unwrap_or_default();
+        let verified = commit.commit.verification.as_ref().is_some_and(|v| v.verified);
+        let marker = if verified { palette.paint(palette.added, " (verified)") } else { String::new() };
+        println!("  {}{} {}", &commit.sha[..commit.sha.len().min(7)], marker, subject);
+    }
+}
+
+async fn compare_forks(client: &Client, args: &Args, a: &OwnerRepo, b: &OwnerRepo, palette: &Palette, log: &mut OpLog) {
+    let names = format!("{}/{}...{}/{}", a.owner, a.repo, b.owner, b.repo);
+    let result = async {
+        let a_branch = default_branch(client, a).await?;
+        let b_branch = default_branch(client, b).await?;
+        let a_ref = format!("{}:{}", a.owner, a_branch);
+        let b_ref = format!("{}:{}", b.owner, b_branch);
+        // Each direction is one compare, github lists only the commits of the head
+        let b_only = unique_commits(client, a, &a_ref, &b_ref).await?;
+        let a_only = unique_commits(client, a, &b_ref, &a_ref).await?;
+        Ok::<_, ClientError>((a_branch, b_branch, a_only, b_only))
+    };
+
+    let (a_branch, b_branch, a_only, b_only) = match result.await {
+        Ok(result) => result,
+        Err(e) => {
+            log.log("compare-forks", &names, "failed", Some(&e.to_string()));
+            api_error(&e, args);
+            exit(1);
+        }
+    };
+    log.log("compare-forks", &names, "ok", Some(&format!("+{}/+{}", a_only.ahead_by, b_only.ahead_by)));
+
+    let merge_base = &b_only.merge_base_commit.sha;
+    println!("merge-base {}", &merge_base[..merge_base.len().min(7)]);
+    println!("{}/{} {} | {} unique", a.owner, a.repo, a_branch, a_only.ahead_by);
+    print_commits(&a_only.commits, palette);
+    println!("{}/{} {} | {} unique", b.owner, b.repo, b_branch, b_only.ahead_by);
+    print_commits(&b_only.commits, palette);
+}
+
+fn unify_remote_name(name: &str) -> String {
+    let mut out: String = name.to_string();
+    out.insert_str(0, REMOTE_PREFIX);
+    out.replace("/", "_")
+}
+
+/// `name` cut to `max_length` bytes with a hash of the whole name, see `--max-name-length`.
+fn truncate_remote_name(name: String, max_length: u16) -> String {
+    let max_length = max_length as usize;
+    if name.len() <= max_length {
+        return name;
+    }
+    // FNV-1a, unlike the std hasher it is the same in every build
+    let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193));
+    let suffix = format!("-{:08x}", hash);
+    let mut end = max_length - suffix.len();
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &name[..end], suffix)
+}
+
+/// Name of the remote for `fork` of `source` before `--max-name-length`, see
+/// `--add-prefix-per-source`.
+fn full_remote_name(args: &Args, source: &str, fork: &MinimalRepository) -> String {
+    let name = unify_remote_name(&fork.full_name);
+    if !args.add_prefix_per_source {
+        return name;
+    }
+    format!("{}__{}", unify_remote_name(source), &name[REMOTE_PREFIX.len()..])
+}
+
+/// Name of the remote for `fork` of `source`.
+fn fork_remote_name(args: &Args, source: &str, fork: &MinimalRepository) -> String {
+    truncate_remote_name(full_remote_name(args, source, fork), args.max_name_length)
+}
+
+/// `owner/repo` of the rgf remote `name` with `url`, the inverse of `unify_remote_name`.
+///
+/// Repository names may contain `_` and `__`, only the url tells the names of
+/// `--add-prefix-per-source` apart.
+fn remote_full_name(name: &str, url: &str) -> Option<String> {
+    // Local paths and file urls have no owner
+    if let Some(owner_repo) = url_owner_repo(url).filter(|_| !hosts::host(url).is_empty()) {
+        return Some(format!("{}/{}", owner_repo.owner, owner_repo.repo));
+    }
+    let (owner, repo) = name.strip_prefix(REMOTE_PREFIX)?.split_once('_')?;
+    (!owner.is_empty() && !repo.is_empty() && !repo.contains("__")).then(|| format!("{}/{}", owner, repo))
+}
+
+fn open_repository(args: &Args) -> git2::Repository {
+    match discover_repository(args.repo_path.as_deref()) {
+        Ok(repo) => repo,
+        Err((code, message)) => {
+            println!("Error: {}", message);
+            exit(code);
+        },
+    }
+}
+
+/// Repository containing `repo_path`, or else the current directory, see `--repo-path`.
+///
+/// The error comes with the exit code for it.
+fn discover_repository(repo_path: Option<&Path>) -> Result<git2::Repository, (i32, String)> {
+    let path = repo_path.map(Path::to_path_buf).unwrap_or(PathBuf::from("."));
+    match git2::Repository::discover(&path) {
+        Ok(repo) => Ok(repo),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Err(not_in_repository(repo_path)),
+        Err(e) => Err((1, format!("Failed to open repository: {}", e))),
+    }
+}
+
+/// Exit code and message for `repo_path` not being inside a repository, `None` for the current
+/// directory.
+fn not_in_repository(repo_path: Option<&Path>) -> (i32, String) {
+    let message = match repo_path {
+        Some(path) => format!("{} is not inside a git repository", path.display()),
+        None => "Not inside a git repository; run from within one or pass --repo-path".to_string(),
+    };
+    (EXIT_NOT_IN_REPO, message)
+}
+
+/// Name and url of all remotes managed by rgf.
+fn rgf_remotes(repo: &git2::Repository) -> Vec<(String, String)> {
+    let names = match repo.remotes() {
+        Ok(remotes) => remotes,
+        Err(e) => {
+            println!("Error: Failed to get remotes: {}", e);
+            exit(1);
+        },
+    };
+
+    names.iter()
+        .flatten()
+        .filter(|name| name.starts_with(REMOTE_PREFIX))
+        .filter_map(|name| {
+            let remote = repo.find_remote(name).ok()?;
+            Some((name.to_string(), remote.url()?.to_string()))
+        })
+        .collect()
+}
+
+/// Owner and repository of a github clone url, either https or ssh.
+fn url_owner_repo(url: &str) -> Option<OwnerRepo> {
+    let path = url.strip_prefix("git@github.com:")
+        .or_else(|| url.split_once("://").and_then(|(_, rest)| rest.split_once('/')).map(|(_, path)| path))?;
+    OwnerRepo::new(path.trim_end_matches('/').trim_end_matches(".git")).ok()
+}
+
+/// `url` without protocol, user, trailing `.git` and case, so the https and ssh urls of a
+/// repository are the same.
+fn normalize_url(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest.to_string(),
+        // scp like syntax of ssh, `git@github.com:owner/repo`
+        None => url.replacen(':', "/", 1),
+    };
+    let rest = rest.rsplit_once('@').filter(|(user, _)| !user.contains('/')).map(|(_, host)| host).unwrap_or(&rest);
+    rest.trim_end_matches('/').trim_end_matches(".git").to_string()
+}
+
+/// Name and url of the rgf remote `name` with `url` after its fork `old` was renamed to `fork`.
+fn renamed_remote(name: &str, url: &str, old: &str, fork: &FullRepository, max_length: u16) -> (String, String) {
+    // Keeps the source of --add-prefix-per-source, if the remote has one
+    let old_name = unify_remote_name(old);
+    let prefix = name.strip_suffix(&old_name[REMOTE_PREFIX.len()..]).unwrap_or(REMOTE_PREFIX);
+    let new_name = truncate_remote_name(format!("{}{}", prefix, &unify_remote_name(&fork.full_name)[REMOTE_PREFIX.len()..]), max_length);
+    // Keep the protocol the remote was added with
+    let new_url = if url.starts_with("git@") { fork.ssh_url.clone() } else { fork.clone_url.clone() };
+    (new_name, new_url)
+}
+
+/// Points the remote `name` at `new_url` and renames it to `new_name`, keeping its fetched refs.
+fn move_remote(repo: &git2::Repository, name: &str, new_name: &str, new_url: &str) -> Result<(), git2::Error> {
+    // Checked first, the url would already point at the renamed fork otherwise
+    if new_name != name && repo.find_remote(new_name).is_ok() {
+        return Err(git2::Error::from_str(&format!("remote {} already exists", new_name)));
+    }
+    repo.remote_set_url(name, new_url)?;
+    if new_name != name {
+        // Also moves the fetched refs and the default refspec
+        repo.remote_rename(name, new_name)?;
+    }
+    Ok(())
+}
+
+/// `url` with a lowercase host, without trailing slashes and with a single `.git` suffix.
+fn canonical_url(url: &str) -> String {
+    let mut path = url.trim().trim_end_matches('/');
+    while let Some(stripped) = path.strip_suffix(".git") {
+        path = stripped.trim_end_matches('/');
+    }
+    // The host ends at the first `/` after the protocol, or at the `:` of the scp like syntax
+    let (host_start, host_end) = match path.find("://") {
+        Some(protocol) => (protocol + 3, path[protocol + 3..].find('/').map(|end| protocol + 3 + end)),
+        None => (0, path.find(':')),
+    };
+    let host_end = host_end.unwrap_or(path.len());
+    // The user in front of the host keeps its case
+    let host_start = path[host_start..host_end].rfind('@').map(|at| host_start + at + 1).unwrap_or(host_start);
+    format!("{}{}{}.git", &path[..host_start], path[host_start..host_end].to_lowercase(), &path[host_end..])
+}
+
+/// Url of the remote of `fork`, see `--ssh` and `--normalize-urls`.
+fn fork_url(fork: &MinimalRepository, args: &Args) -> String {
+    let url = if args.ssh { &fork.ssh_url } else { &fork.clone_url };
+    if args.normalize_urls {
+        canonical_url(url)
+    } else {
+        url.clone()
+    }
+}
+
+/// Looks up the fork of every rgf remote and follows renames, see `--follow-renamed-forks`.
+async fn follow_renamed_forks(client: &Client, args: &Args, palette: &Palette, log: &mut OpLog) {
+    let repo = open_repository(args);
+    let remotes = rgf_remotes(&repo);
+
+    let mut renamed = 0;
+    let mut unchanged = 0;
+    let mut failed = 0;
+    for (name, url) in remotes {
+        let Some(old) = url_owner_repo(&url) else {
+            log.log("follow-rename", &name, "failed", Some("no github url"));
+            failed += 1;
+            println!("{}", palette.paint(palette.failed, &format!("Failed to look up {}: no github url {}", name, url)));
+            continue;
+        };
+
+        // github redirects from the old name, the client follows it to the new one
+        let fork = match client.repos().get(&old.owner, &old.repo).await {
+            Ok(response) => response.body,
+            Err(ClientError::RateLimited { duration }) => {
+                log.log("follow-rename", &name, "rate-limited", Some(&format!("{} seconds", duration)));
+                println!("Rate limited for the next {} seconds, skipping remaining remotes", duration);
+                break;
+            },
+            Err(ClientError::HttpError { status: StatusCode::NOT_FOUND, .. }) => {
+                log.log("follow-rename", &name, "gone", None);
+                failed += 1;
+                println!("{}", palette.paint(palette.failed, &format!("gone {}: {}/{} does not exist anymore", name, old.owner, old.repo)));
+                continue;
+            },
+            Err(e) => {
+                log.log("follow-rename", &name, "failed", Some(&e.to_string()));
+                failed += 1;
+                println!("{}", palette.paint(palette.failed, &format!("Failed to look up {}: {}", name, e)));
+                continue;
+            },
+        };
+
+        if fork.full_name.eq_ignore_ascii_case(&format!("{}/{}", old.owner, old.repo)) {
+            unchanged += 1;
+            continue;
+        }
+
+        let (new_name, new_url) = renamed_remote(&name, &url, &format!("{}/{}", old.owner, old.repo), &fork, args.max_name_length);
+        if args.dry_run {
+            log.log("follow-rename", &name, "planned", Some(&new_name));
+            renamed += 1;
+            println!("{}", palette.paint(palette.planned, &format!("(~) {} -> {}", name, new_name)));
+            continue;
+        }
+
+        match move_remote(&repo, &name, &new_name, &new_url) {
+            Ok(_) => {
+                log.log("follow-rename", &name, "renamed", Some(&new_name));
+                renamed += 1;
+                println!("{}", palette.paint(palette.added, &format!("renamed {} -> {}", name, new_name)));
+            },
+            Err(e) => {
+                log.log("follow-rename", &name, "failed", Some(e.message()));
+                failed += 1;
+                println!("{}", palette.paint(palette.failed, &format!("Failed to rename {} -> {}: {}", name, new_name, e.message())));
+            },
+        }
+    }
+    println!("renamed:{} unchanged:{} failed:{}{}", renamed, unchanged, failed, if args.dry_run { " (dry-run)" } else { "" });
+}
+
+/// Whether `e` of a git network operation is likely gone when trying again.
+fn is_transient_network_error(e: &git2::Error) -> bool {
+    if !matches!(e.class(), git2::ErrorClass::Net | git2::ErrorClass::Os | git2::ErrorClass::Ssl | git2::ErrorClass::Http) {
+        return false;
+    }
+    // libgit2 has no codes for these, only its messages tell
+    let message = e.message().to_lowercase();
+    ["failed to resolve", "reset", "timed out", "timeout", "temporar", "broken pipe", "early eof", "unexpected eof"]
+        .iter()
+        .any(|transient| message.contains(transient))
+}
+
+/// Runs the git network operation `op` on `name` again after transient errors, up to `retries`
+/// times, see `--retry-network-errors`.
+fn with_network_retries<T>(name: &str, retries: u32, mut op: impl FnMut() -> Result<T, git2::Error>) -> Result<T, git2::Error> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(e) if attempt < retries && is_transient_network_error(&e) => {
+                let wait = NETWORK_RETRY_WAIT * 2u32.pow(attempt);
+                eprintln!("Warning: {}: {}, retrying in {}s", name, e.message(), wait.as_secs());
+                thread::sleep(wait);
+                attempt += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Connects to `url` and lists its refs, i.e. a `git ls-remote` without output.
+///
+/// With `no_verify_ssl` any certificate of the server is accepted.
+fn check_remote(url: &str, no_verify_ssl: bool) -> Result<(), git2::Error> {
+    let mut remote = git2::Remote::create_detached(url)?;
+    let mut callbacks = git2::RemoteCallbacks::new();
+    if no_verify_ssl {
+        callbacks.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
+    }
+    let connection = remote.connect_auth(git2::Direction::Fetch, Some(callbacks), None)?;
+    connection.list()?;
+    Ok(())
+}
+
+/// Queue of the remote workers, limited per host by `--max-concurrent-hosts`.
+fn remote_queue(remotes: Vec<(String, String)>, args: &Args) -> hosts::HostQueue<(String, String)> {
+    let items = remotes.into_iter().map(|(name, url)| (hosts::host(&url), (name, url))).collect();
+    hosts::HostQueue::new(items, args.max_concurrent_hosts.map(|limit| limit as usize))
+}
+
+fn verify_remotes(args: &Args, palette: &Palette, log: &mut OpLog) {
+    let repo = open_repository(args);
+
+    // The workers share the queue of remotes and report back through the channel, git2 remotes
+    // of a repository can not be shared between threads.
+    let mut remotes = rgf_remotes(&repo);
+    processing_order(&mut remotes, args);
+    let queue = Arc::new(remote_queue(remotes, args));
+    let (tx, rx) = mpsc::channel();
+    let no_verify_ssl = args.no_verify_ssl;
+    let retries = if args.retry_network_errors { args.max_retries } else { 0 };
+    for _ in 0..args.jobs.max(1) {
+        let queue = Arc::clone(&queue);
+        let tx = tx.clone();
+        thread::spawn(move || {
+            while let Some((_slot, (name, url))) = queue.next() {
+                let result = with_network_retries(&name, retries, || check_remote(&url, no_verify_ssl));
+                if tx.send((name, result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    let mut reachable = 0;
+    let mut unreachable = 0;
+    for (name, result) in rx {
+        match result {
+            Ok(_) => {
+                reachable += 1;
+                log.log("verify", &name, "reachable", None);
+                println!("{}", palette.paint(palette.added, &format!("reachable {}", name)));
+            },
+            Err(e) => {
+                unreachable += 1;
+                log.log("verify", &name, "unreachable", Some(e.message()));
+                println!("{}", palette.paint(palette.failed, &format!("unreachable {}: {}", name, e.message())));
+            },
+        }
+    }
+    println!("reachable:{} unreachable:{}", reachable, unreachable);
+}
+
+/// Fetches `name`, only the last `shallow` commits if given, and returns the commit of its head
+/// before and after the fetch.
+///
+/// The bytes received are added to `received`, also those of a failed fetch.
+fn update_remote(repo: &git2::Repository, name: &str, no_verify_ssl: bool, shallow: Option<i32>, received: &AtomicU64) -> Result<(Option<git2::Oid>, Option<git2::Oid>), git2::Error> {
+    let before = remote_head(repo, name);
+    let transferred = Cell::new(0);
+    let mut callbacks = git2::RemoteCallbacks::new();
+    if no_verify_ssl {
+        callbacks.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
+    }
+    callbacks.transfer_progress(|progress| {
+        transferred.set(progress.received_bytes());
+        true
+    });
+    let mut options = git2::FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    if let Some(depth) = shallow {
+        options.depth(depth);
+    }
+    // No refspecs given, so the configured ones of the remote are used
+    let fetched = repo.find_remote(name)?.fetch::<&str>(&[], Some(&mut options), None);
+    received.fetch_add(transferred.get() as u64, Ordering::Relaxed);
+    fetched?;
+    Ok((before, remote_head(repo, name)))
+}
+
+/// Lists the refs of `name` at `url` without fetching and returns the commit of its head before
+/// and the one a fetch would bring, see `--dry-run-json`.
+fn peek_remote(repo: &git2::Repository, name: &str, url: &str, no_verify_ssl: bool) -> Result<(Option<git2::Oid>, Option<git2::Oid>), git2::Error> {
+    let before = remote_head(repo, name);
+    let mut remote = git2::Remote::create_detached(url)?;
+    let mut callbacks = git2::RemoteCallbacks::new();
+    if no_verify_ssl {
+        callbacks.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
+    }
+    let connection = remote.connect_auth(git2::Direction::Fetch, Some(callbacks), None)?;
+    let heads = connection.list()?;
+    // The same order as remote_head
+    let after = ["HEAD", "refs/heads/main", "refs/heads/master"].iter()
+        .find_map(|branch| heads.iter().find(|head| head.name() == *branch).map(|head| head.oid()));
+    Ok((before, after))
+}
+
+/// `bytes` with a binary unit, e.g. `1.5MiB`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", value, UNITS[unit])
+}
+
+/// Whether a failed fetch means the fork is gone, github asks for authentication for deleted
+/// public repositories.
+fn is_deleted_fork(e: &git2::Error) -> bool {
+    matches!(e.code(), git2::ErrorCode::Auth | git2::ErrorCode::NotFound)
+}
+
+/// Current fork of the rgf remote `name` with `url`, found by the url or else by the name.
+///
+/// Needs a client following redirects, github answers with one for a renamed repository. `None`
+/// if github does not know the fork anymore.
+async fn remote_fork(client: &Client, name: &str, url: &str) -> Result<Option<(String, FullRepository)>, String> {
+    let Some(full_name) = remote_full_name(name, url) else {
+        return Err("not a name of rgf".to_string());
+    };
+    let owner_repo = OwnerRepo::new(&full_name)?;
+    match client.repos().get(&owner_repo.owner, &owner_repo.repo).await {
+        Ok(response) => Ok(Some((full_name, response.body))),
+        Err(ClientError::HttpError { status: StatusCode::NOT_FOUND, .. }) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+async fn update_remotes(client: &Client, args: &Args, palette: &Palette, log: &mut OpLog, planned: &mut Vec<PlannedOp>) {
+    let repo = open_repository(args);
+
+    // Same as for --verify-remotes, the repository is opened for every fetch as it can not be
+    // shared between the threads either
+    let mut remotes = rgf_remotes(&repo);
+    processing_order(&mut remotes, args);
+    let urls: HashMap<String, String> = remotes.iter().cloned().collect();
+    let queue = Arc::new(remote_queue(remotes, args));
+    let (tx, rx) = mpsc::channel();
+    let no_verify_ssl = args.no_verify_ssl;
+    let retries = if args.retry_network_errors { args.max_retries } else { 0 };
+    let peek = args.dry_run_json;
+    let shallow = args.shallow;
+    // Bytes received by all fetches together
+    let received = Arc::new(AtomicU64::new(0));
+    for _ in 0..args.jobs.max(1) {
+        let queue = Arc::clone(&queue);
+        let tx = tx.clone();
+        let path = repo.path().to_path_buf();
+        let received = Arc::clone(&received);
+        thread::spawn(move || {
+            while let Some((_slot, (name, url))) = queue.next() {
+                let result = git2::Repository::open(&path).and_then(|repo| with_network_retries(&name, retries, || {
+                    if peek { peek_remote(&repo, &name, &url, no_verify_ssl) } else { update_remote(&repo, &name, no_verify_ssl, shallow, &received) }
+                }));
+                if tx.send((name, result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    // With --dry-run-json stdout is only the plan, the errors go to stderr
+    let item = |line: String| {
+        if !args.dry_run_json {
+            println!("{}", line);
+        }
+    };
+    let failure = |line: String| {
+        if args.dry_run_json {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    };
+    let short = |oid: Option<git2::Oid>| oid.map(|oid| oid.to_string()[..7].to_string()).unwrap_or("-".to_string());
+    let mut updated = 0;
+    let mut unchanged = 0;
+    let mut failed = 0;
+    let mut pruned = 0;
+    let mut renamed = 0;
+    // Removed after all fetches, once confirmed
+    let mut deleted = Vec::new();
+    for (name, result) in rx {
+        let (name, result) = match result {
+            Err(e) if args.prune && is_deleted_fork(&e) => {
+                // A renamed fork is fetched again under its new name instead of being pruned
+                let url = &urls[&name];
+                match remote_fork(client, &name, url).await {
+                    Ok(Some((old, fork))) if !fork.full_name.eq_ignore_ascii_case(&old) => {
+                        let (new_name, new_url) = renamed_remote(&name, url, &old, &fork, args.max_name_length);
+                        if args.dry_run {
+                            renamed += 1;
+                            log.log("update", &name, "planned", Some(&new_name));
+                            planned.push(PlannedOp::new("rename", &name).to(&new_name).url(&new_url));
+                            item(palette.paint(palette.planned, &format!("(~) {} -> {}", name, new_name)));
+                            continue;
+                        }
+                        match move_remote(&repo, &name, &new_name, &new_url) {
+                            Ok(_) => {
+                                renamed += 1;
+                                log.log("update", &name, "renamed", Some(&new_name));
+                                item(palette.paint(palette.added, &format!("renamed {} -> {}", name, new_name)));
+                                let result = with_network_retries(&new_name, retries, || update_remote(&repo, &new_name, no_verify_ssl, shallow, &received));
+                                (new_name, result)
+                            },
+                            Err(rename_error) => {
+                                log.log("update", &name, "failed", Some(rename_error.message()));
+                                failure(palette.paint(palette.failed, &format!("Failed to rename {} -> {}: {}", name, new_name, rename_error.message())));
+                                (name, Err(e))
+                            },
+                        }
+                    },
+                    Ok(_) => (name, Err(e)),
+                    Err(lookup_error) => {
+                        // Without knowing better the remote is kept
+                        failed += 1;
+                        log.log("update", &name, "failed", Some(e.message()));
+                        planned.push(PlannedOp::new("fail", &name).detail(e.message()));
+                        failure(palette.paint(palette.failed, &format!("failed {}: {}, not pruned as the lookup failed: {}", name, e.message(), lookup_error)));
+                        continue;
+                    },
+                }
+            },
+            result => (name, result),
+        };
+
+        match result {
+            Ok((before, after)) if before != after => {
+                updated += 1;
+                let range = format!("{}..{}", short(before), short(after));
+                log.log("update", &name, "updated", Some(&range));
+                planned.push(PlannedOp::new("update", &name).detail(&range));
+                item(palette.paint(palette.added, &format!("updated {} {}", name, range)));
+            },
+            Ok(_) => {
+                unchanged += 1;
+                log.log("update", &name, "unchanged", None);
+                planned.push(PlannedOp::new("skip", &name).detail("unchanged"));
+                item(palette.paint(palette.existing, &format!("unchanged {}", name)));
+            },
+            Err(e) => {
+                failed += 1;
+                log.log("update", &name, "failed", Some(e.message()));
+                failure(palette.paint(palette.failed, &format!("failed {}: {}", name, e.message())));
+
+                if !args.prune || !is_deleted_fork(&e) {
+                    planned.push(PlannedOp::new("fail", &name).detail(e.message()));
+                    continue;
+                }
+                if args.dry_run {
+                    pruned += 1;
+                    planned.push(PlannedOp::new("prune", &name).detail(e.message()));
+                    item(palette.paint(palette.planned, &format!("(-) {}", name)));
+                    continue;
+                }
+                deleted.push(name);
+            },
+        }
+    }
+    if !deleted.is_empty() && confirm_removal(args, &deleted, "of deleted forks") {
+        pruned += remove_remotes(&repo, &deleted, "prune", palette, log);
+    }
+
+    let pruned = if args.prune { format!(" renamed:{} pruned:{}", renamed, pruned) } else { String::new() };
+    if !args.dry_run_json {
+        let received = format_bytes(received.load(Ordering::Relaxed));
+        println!("updated:{} unchanged:{} failed:{}{} received:{}{}", updated, unchanged, failed, pruned, received, if args.dry_run { " (dry-run)" } else { "" });
+    }
+}
+
+/// `value` as JSON, indented with `--json-pretty`.
+fn to_json<T: Serialize>(value: &T, args: &Args) -> String {
+    let json = if args.json_pretty { serde_json::to_string_pretty(value) } else { serde_json::to_string(value) };
+    json.unwrap()
+}
+
+/// `text` cut to `--abbrev` characters.
+fn abbrev(text: &str, args: &Args) -> String {
+    match args.abbrev {
+        Some(max) if text.chars().count() > max as usize => {
+            let mut out: String = text.chars().take(max as usize - 1).collect();
+            out.push('…');
+            out
+        },
+        _ => text.to_string(),
+    }
+}
+
+/// Description of `fork` on one line, as stored by `--store-description`.
+fn remote_description(fork: &MinimalRepository) -> Option<String> {
+    let description = fork.description.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!description.is_empty()).then_some(description)
+}
+
+fn list_remotes(args: &Args) {
+    let repo = open_repository(args);
+    let config = repo.config().ok();
+    let records: Vec<RemoteRecord> = rgf_remotes(&repo).into_iter()
+        .map(|(name, url)| {
+            let description = config.as_ref()
+                .filter(|_| args.verbose)
+                .and_then(|config| config.get_string(&format!("remote.{}.description", name)).ok());
+            let full_name = if args.strip_prefix_on_list { remote_full_name(&name, &url) } else { None };
+            RemoteRecord { name, full_name, url, description }
+        })
+        .collect();
+
+    match args.format {
+        Format::Text => {
+            let rows: Vec<Vec<String>> = records.into_iter()
+                .map(|record| {
+                    let mut row = vec![abbrev(&record.name, args)];
+                    if args.strip_prefix_on_list {
+                        row.push(abbrev(record.full_name.as_deref().unwrap_or("-"), args));
+                    }
+                    row.push(abbrev(&record.url, args));
+                    row.extend(record.description);
+                    row
+                })
+                .collect();
+            let mut out = String::new();
+            for line in render_rows(&rows, args.align()) {
+                out.push_str(&line);
+                out.push('\n');
+            }
+            pager::show(&out, !args.no_pager);
+        },
+        Format::Json => {
+            println!("{}", to_json(&records, args));
+        },
+    }
+}
+
+/// Commit a fetched remote points at, taken from its `HEAD` or the usual default branches.
+fn remote_head(repo: &git2::Repository, name: &str) -> Option<git2::Oid> {
+    ["HEAD", "main", "master"].iter()
+        .find_map(|branch| repo.refname_to_id(&format!("refs/remotes/{}/{}", name, branch)).ok())
+}
+
+fn migrate_prefix(args: &Args, palette: &Palette, log: &mut OpLog, old: &str, new: &str) {
+    let repo = open_repository(args);
+    let names: Vec<String> = match repo.remotes() {
+        Ok(remotes) => remotes.iter().flatten().filter(|name| name.starts_with(old)).map(str::to_string).collect(),
+        Err(e) => {
+            println!("Error: Failed to get remotes: {}", e);
+            exit(1);
+        },
+    };
+
+    let mut renamed = 0;
+    let mut failed = 0;
+    for name in names {
+        let new_name = format!("{}{}", new, &name[old.len()..]);
+        // Checked up front, so a dry run reports it as well
+        if repo.find_remote(&new_name).is_ok() {
+            log.log("migrate-prefix", &name, "failed", Some("exists"));
+            failed += 1;
+            println!("{}", palette.paint(palette.failed, &format!("Failed to rename {} -> {}: remote {} already exists", name, new_name, new_name)));
+            continue;
+        }
+        if args.dry_run {
+            log.log("migrate-prefix", &name, "planned", Some(&new_name));
+            renamed += 1;
+            println!("{}", palette.paint(palette.planned, &format!("(~) {} -> {}", name, new_name)));
+            continue;
+        }
+        match repo.remote_rename(&name, &new_name) {
+            Ok(problems) => {
+                log.log("migrate-prefix", &name, "renamed", Some(&new_name));
+                renamed += 1;
+                println!("{}", palette.paint(palette.added, &format!("renamed {} -> {}", name, new_name)));
+                for refspec in problems.iter().flatten() {
+                    eprintln!("Warning: Fetch refspec {} of {} was not renamed", refspec, new_name);
+                }
+            },
+            Err(e) => {
+                log.log("migrate-prefix", &name, "failed", Some(e.message()));
+                failed += 1;
+                println!("{}", palette.paint(palette.failed, &format!("Failed to rename {} -> {}: {}", name, new_name, e.message())));
+            },
+        }
+    }
+    println!("renamed:{} failed:{}{}", renamed, failed, if args.dry_run { " (dry-run)" } else { "" });
+}
+
+/// Outcome of merging `theirs` into `HEAD`, see `--merge-preview`.
+enum MergePreview {
+    UpToDate,
+    FastForward,
+    Clean,
+    Conflict(Vec<String>),
+}
+
+fn preview_merge(repo: &git2::Repository, theirs: git2::Oid) -> Result<MergePreview, git2::Error> {
+    let (analysis, _) = repo.merge_analysis(&[&repo.find_annotated_commit(theirs)?])?;
+    if analysis.is_up_to_date() {
+        return Ok(MergePreview::UpToDate);
+    }
+    if analysis.is_fast_forward() {
+        return Ok(MergePreview::FastForward);
+    }
+
+    let ours = repo.head()?.peel_to_commit()?;
+    let index = repo.merge_commits(&ours, &repo.find_commit(theirs)?, None)?;
+    if !index.has_conflicts() {
+        return Ok(MergePreview::Clean);
+    }
+    let mut paths: Vec<String> = index.conflicts()?
+        .flatten()
+        // One side may have deleted the file, any side names the path
+        .filter_map(|conflict| conflict.our.or(conflict.their).or(conflict.ancestor))
+        .map(|entry| String::from_utf8_lossy(&entry.path).to_string())
+        .collect();
+    paths.sort();
+    paths.dedup();
+    Ok(MergePreview::Conflict(paths))
+}
+
+/// Commits reachable from `theirs` but not from `HEAD`, newest first.
+fn local_unique_commits(repo: &git2::Repository, theirs: git2::Oid, first_parent: bool) -> Result<Vec<git2::Oid>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.push(theirs)?;
+    walk.hide_head()?;
+    if first_parent {
+        walk.simplify_first_parent()?;
+    }
+    walk.collect()
+}
+
+fn merge_preview(args: &Args, palette: &Palette, name: &str) {
+    let repo = open_repository(args);
+    let Some(theirs) = remote_head(&repo, name) else {
+        println!("Error: {} is not fetched, fetch it first, e.g. with --update", name);
+        exit(1);
+    };
+
+    let preview = preview_merge(&repo, theirs).and_then(|preview| Ok((preview, local_unique_commits(&repo, theirs, args.first_parent)?)));
+    let (preview, commits) = match preview {
+        Ok(preview) => preview,
+        Err(e) => {
+            println!("Error: Failed to preview the merge of {}: {}", name, e.message());
+            exit(1);
+        },
+    };
+    match preview {
+        MergePreview::UpToDate => println!("{}", palette.paint(palette.existing, &format!("up-to-date {}", name))),
+        MergePreview::FastForward => println!("{}", palette.paint(palette.added, &format!("fast-forward {}: {} commits", name, commits.len()))),
+        MergePreview::Clean => println!("{}", palette.paint(palette.added, &format!("clean {}: {} commits", name, commits.len()))),
+        MergePreview::Conflict(paths) => {
+            println!("{}", palette.paint(palette.failed, &format!("conflict {}: {} paths, {} commits", name, paths.len(), commits.len())));
+            for path in paths {
+                println!("  {}", path);
+            }
+        },
+    }
+    if args.verbose {
+        for oid in commits {
+            let summary = repo.find_commit(oid).ok().and_then(|commit| commit.summary().map(str::to_string)).unwrap_or_default();
+            println!("  {} {}", &oid.to_string()[..7], summary);
+        }
+    }
+}
+
+/// Whether the remotes `names` may be removed, see `--assume-yes-destructive`.
+fn confirm_removal(args: &Args, names: &[String], what: &str) -> bool {
+    if args.assume_yes_destructive {
+        return true;
+    }
+    if !(stdin().is_terminal() && stderr().is_terminal()) {
+        eprintln!("Warning: Not removing {} remotes {} without a terminal, pass --assume-yes-destructive to remove them", names.len(), what);
+        return false;
+    }
+
+    eprintln!("Remotes to remove {}:", what);
+    for name in names {
+        eprintln!("  {}", name);
+    }
+    let answer = dialoguer::Input::<String>::new()
+        .with_prompt(format!("Type 'remove' to remove these {} remotes", names.len()))
+        .allow_empty(true)
+        .interact_text();
+    let confirmed = answer.is_ok_and(|answer| answer.trim() == "remove");
+    if !confirmed {
+        eprintln!("No remotes removed");
+    }
+    confirmed
+}
+
+/// Removes the remotes `names`, returns how many were removed.
+fn remove_remotes(repo: &git2::Repository, names: &[String], op: &str, palette: &Palette, log: &mut OpLog) -> usize {
+    let mut removed = 0;
+    for name in names {
+        match repo.remote_delete(name) {
+            Ok(_) => {
+                removed += 1;
+                log.log(op, name, "removed", None);
+                println!("{}", palette.paint(palette.removed, &format!("Remote {} removed", name)));
+            },
+            Err(e) => {
+                log.log(op, name, "failed", Some(e.message()));
+                println!("{}", palette.paint(palette.failed, &format!("Failed to remove remote {}: {}", name, e)));
+            },
+        }
+    }
+    removed
+}
+
+fn dedup_by_content(args: &Args, palette: &Palette, log: &mut OpLog) {
+    let repo = open_repository(args);
+
+    // Sorted by commit and name, so the kept remote of a group is always the same one
+    let mut groups: BTreeMap<git2::Oid, Vec<String>> = BTreeMap::new();
+    let mut not_fetched = Vec::new();
+    let mut dupes = Vec::new();
+    for (name, _) in rgf_remotes(&repo) {
+        match remote_head(&repo, &name) {
+            Some(oid) => groups.entry(oid).or_default().push(name),
+            None => not_fetched.push(name),
+        }
+    }
+
+    for (oid, names) in groups.iter_mut().filter(|(_, names)| names.len() > 1) {
+        names.sort();
+        let short = oid.to_string()[..7].to_string();
+        println!("{}", palette.paint(palette.existing, &format!("= {} {}", short, names.join(" "))));
+        log.log("dedup", &names[0], "duplicates", Some(&names[1..].join(" ")));
+
+        if !args.prune_dupes {
+            continue;
+        }
+        for name in &names[1..] {
+            if args.dry_run {
+                println!("{}", palette.paint(palette.planned, &format!("(-) {}", name)));
+                continue;
+            }
+            dupes.push(name.clone());
+        }
+    }
+    if !dupes.is_empty() && confirm_removal(args, &dupes, "with duplicate content") {
+        remove_remotes(&repo, &dupes, "prune-dupe", palette, log);
+    }
+
+    if !not_fetched.is_empty() {
+        println!("not fetched: {}", not_fetched.join(" "));
+    }
+}
+
+/// Prints the api calls a run with `args` makes, see `--explain`.
+fn explain(args: &Args) {
+    // (call, minimal count, count depends on the responses)
+    let mut calls: Vec<(String, usize, Option<&str>)> = Vec::new();
+
+    if args.rate_limit {
+        calls.push(("GET /rate_limit".to_string(), 1, None));
+    }
+
+    if args.follow_renamed_forks {
+        calls.push(("GET /repos/<remote>".to_string(), 0, Some("one per rgf remote")));
+    }
+
+    if let Some(forks) = &args.compare_forks {
+        for fork in forks {
+            calls.push((format!("GET /repos/{}", fork), 1, None));
+        }
+        calls.push((format!("GET /repos/{}/compare/<a>...<b>", forks[0]), 2, None));
+    }
+
+    // Query of the first page of forks, see list_forks
+    let query = format!("page={}&per_page={}&sort={}", args.page, args.per_page, args.sort.github_sort());
+    if let Some(org) = &args.org {
+        calls.push((format!("GET /orgs/{}/repos", org), 1, Some("one per 30 repositories")));
+        calls.push((format!("GET /repos/{}/<repo>/forks?{}", org, query), 0, Some("one per repository with forks")));
+    } else if args.repository.as_deref() == Some(STDIN_REPOSITORY) {
+        calls.push((format!("GET /repos/<repo>/forks?{}", query), 0, Some("one per repository on stdin")));
+    } else if let Some(repository) = &args.repository {
+        if args.root {
+            calls.push((format!("GET /repos/{}", repository), 1, None));
+        }
+        let depends = (args.max_pages > 1).then_some("one per page, up to --max-pages");
+        let repository = if args.root { "<root>" } else { repository.as_str() };
+        calls.push((format!("GET /repos/{}/forks?{}", repository, query), 1, depends));
+    }
+
+    if args.include_source || args.highlight_shared_topics {
+        match &args.org {
+            Some(org) => calls.push((format!("GET /repos/{}/<repo>", org), 0, Some("one per repository with forks"))),
+            None if args.repository.as_deref() == Some(STDIN_REPOSITORY) => {
+                calls.push(("GET /repos/<repo>".to_string(), 0, Some("one per repository on stdin")));
+            },
+            None => calls.push((format!("GET /repos/{}", args.repository.as_deref().unwrap_or_default()), 1, None)),
+        }
+    }
+
+    if args.with_divergences() {
+        calls.push(("GET /repos/<source>".to_string(), 1, Some("one per source repository")));
+        if let Some(CompareBase::Commit(sha)) = &args.compare_base {
+            calls.push((format!("GET /repos/<source>/commits/{}", sha), 1, Some("one per source repository")));
+        }
+        calls.push(("GET /repos/<source>/compare/<source>...<fork>".to_string(), 0, Some("one per selected fork")));
+    }
+
+    if args.with_contributors() {
+        calls.push(("GET /repos/<fork>/contributors".to_string(), 0, Some("one per selected fork")));
+    }
+
+    if args.enrich {
+        calls.push(("GET /repos/<fork>".to_string(), 0, Some("one per fetched fork")));
+    }
+
+    if args.with_upstream_prs() {
+        calls.push(("GET /search/issues".to_string(), 0, Some("one per selected fork, at most 30 per minute")));
+    }
+
+    if args.with_last_commit() {
+        calls.push(("GET /repos/<fork>/commits/HEAD".to_string(), 0, Some("one per selected fork")));
+    }
+
+    if args.with_topics() {
+        calls.push(("GET /repos/<fork>/topics".to_string(), 0, Some("one per selected fork")));
+    }
+
+    if args.with_relationships() && !args.enrich {
+        calls.push(("GET /repos/<fork>".to_string(), 0, Some("one per selected fork")));
+    }
+
+    let mut total = 0;
+    let mut open_ended = false;
+    for (call, count, depends) in &calls {
+        total += count;
+        match depends {
+            Some(depends) => {
+                open_ended = true;
+                println!("{} | {}+ ({})", call, count, depends);
+            },
+            None => println!("{} | {}", call, count),
+        }
+    }
+    println!("api calls: {}{}", total, if open_ended { "+" } else { "" });
+}
+
+/// Lets the user pick forks on the terminal, all others are dropped from `sources`.
+///
+/// Returns false if the selection was aborted.
+fn select_forks(sources: &mut [(String, Vec<MinimalRepository>)]) -> bool {
+    let items: Vec<String> = sources.iter()
+        .flat_map(|(_, forks)| forks)
+        .map(|fork| {
+            let pushed_at = fork.pushed_at.map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or("never".to_string());
+            format!("{} | {} stars | pushed {}", fork.full_name, fork.stargazers_count, pushed_at)
+        })
+        .collect();
+    if items.is_empty() {
+        return true;
+    }
+
+    let chosen = match dialoguer::MultiSelect::new()
+        .with_prompt("Forks to add (space to toggle, enter to confirm)")
+        .items(&items)
+        .interact_opt()
+    {
+        Ok(Some(chosen)) => chosen,
+        Ok(None) => return false,
+        Err(e) => {
+            println!("Error: {}", e);
+            exit(1);
+        }
+    };
+
+    let mut index = 0;
+    for (_, forks) in sources.iter_mut() {
+        forks.retain(|_| {
+            index += 1;
+            chosen.contains(&(index - 1))
+        });
+    }
+    true
+}
+
+/// The changes an add would make, as printed by `--plan --format json`.
+#[derive(Serialize, Debug, Default)]
+struct Plan {
+    add: Vec<String>,
+    keep: Vec<String>,
+    orphan: Vec<String>,
+}
+
+/// Schema version of `--dry-run-json`, changed with every incompatible change of the schema.
+const DRY_RUN_PLAN_VERSION: u32 = 1;
+
+/// One operation of a `--dry-run-json` plan.
+#[derive(Serialize, Debug)]
+struct PlannedOp {
+    op: &'static str,
+    remote: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl PlannedOp {
+    fn new(op: &'static str, remote: &str) -> PlannedOp {
+        PlannedOp { op, remote: remote.to_string(), url: None, to: None, detail: None }
+    }
+
+    fn url(mut self, url: &str) -> PlannedOp {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    fn to(mut self, to: &str) -> PlannedOp {
+        self.to = Some(to.to_string());
+        self
+    }
+
+    fn detail(mut self, detail: &str) -> PlannedOp {
+        self.detail = Some(detail.to_string());
+        self
+    }
+}
+
+/// The document printed by `--dry-run-json`.
+#[derive(Serialize, Debug)]
+struct DryRunPlan<'a> {
+    version: u32,
+    operations: &'a [PlannedOp],
+}
+
+/// Prints the operations collected by `--add`, `--update` and `--sync`, see `--dry-run-json`.
+fn print_dry_run_plan(args: &Args, operations: &[PlannedOp]) {
+    if args.dry_run_json {
+        println!("{}", to_json(&DryRunPlan { version: DRY_RUN_PLAN_VERSION, operations }, args));
+    }
+}
+
+/// The remotes an add would create, keep and leave orphaned.
+fn build_plan(repo: &git2::Repository, args: &Args, sources: &[(String, Vec<MinimalRepository>)], fetched: &HashSet<String>) -> Plan {
+    let existing: HashSet<String> = rgf_remotes(repo).into_iter().map(|(name, _)| name).collect();
+
+    let mut plan = Plan::default();
+    for (source, fork) in sources.iter().flat_map(|(source, forks)| forks.iter().map(move |fork| (source, fork))) {
+        let name = fork_remote_name(args, source, fork);
+        // Forks of several sources may be the same, see add_remotes
+        if plan.add.contains(&name) || plan.keep.contains(&name) {
+            continue;
+        }
+        if existing.contains(&name) {
+            plan.keep.push(name);
+        } else {
+            plan.add.push(name);
+        }
+    }
+    plan.orphan = existing.into_iter().filter(|name| !fetched.contains(name)).collect();
+    plan.orphan.sort();
+    plan
+}
+
+/// Prints the remotes an add would create, keep and leave orphaned, see `--plan`.
+fn plan(args: &Args, palette: &Palette, sources: &[(String, Vec<MinimalRepository>)], fetched: &HashSet<String>) {
+    let plan = build_plan(&open_repository(args), args, sources, fetched);
+    print_plan(args, palette, &plan);
+}
+
+fn print_plan(args: &Args, palette: &Palette, plan: &Plan) {
+    match args.format {
+        Format::Text => {
+            for name in &plan.add {
+                println!("{}", palette.paint(palette.planned, &format!("+ {}", name)));
+            }
+            for name in &plan.keep {
+                println!("{}", palette.paint(palette.existing, &format!("= {}", name)));
+            }
+            for name in &plan.orphan {
+                println!("{}", palette.paint(palette.removed, &format!("- {}", name)));
+            }
+            println!("add:{} keep:{} orphan:{}", plan.add.len(), plan.keep.len(), plan.orphan.len());
+        },
+        Format::Json => println!("{}", to_json(&plan, args)),
+    }
+}
+
+/// Whether the rgf remote `name` with `url` is one of the forks of `sources`.
+///
+/// The source of a remote is only known from its name with `--add-prefix-per-source`. Without, a
+/// fork with the repository name of a source or of one of its listed forks is taken as its fork.
+/// Other remotes are kept, which is the safe side.
+fn is_remote_of_listed_source(args: &Args, name: &str, url: &str, sources: &[(String, Vec<MinimalRepository>)]) -> bool {
+    if args.add_prefix_per_source {
+        return sources.iter().any(|(source, _)| name.starts_with(&format!("{}__", unify_remote_name(source))));
+    }
+    let Some(full_name) = remote_full_name(name, url) else { return false };
+    let repo_of = |full_name: &str| full_name.split_once('/').map(|(_, repo)| repo.to_lowercase()).unwrap_or_default();
+    let repo = repo_of(&full_name);
+    sources.iter().any(|(source, forks)| repo_of(source) == repo || forks.iter().any(|fork| repo_of(&fork.full_name) == repo))
+}
+
+/// Adds the missing remotes and, with `--source-of-truth api`, removes the orphans, see `--sync`.
+///
+/// `complete` tells whether the fork list has all forks, only then orphans are removed.
+fn sync(args: &Args, palette: &Palette, log: &mut OpLog, planned: &mut Vec<PlannedOp>, sources: &[(String, Vec<MinimalRepository>)], fetched: &HashSet<String>, complete: bool) {
+    let repo = open_repository(args);
+    let mut plan = build_plan(&repo, args, sources, fetched);
+    // Remotes of other repositories are none of the sync's business
+    let urls: HashMap<String, String> = rgf_remotes(&repo).into_iter().collect();
+    plan.orphan.retain(|name| urls.get(name).is_some_and(|url| is_remote_of_listed_source(args, name, url, sources)));
+    let remove = args.source_of_truth == SourceOfTruth::Api && !plan.orphan.is_empty();
+    if remove && !complete {
+        eprintln!("Warning: The fork list may be incomplete, see --page, --max-pages and the errors above, no remote is removed");
+    }
+    let remove = remove && complete;
+    if args.source_of_truth == SourceOfTruth::Git && !plan.orphan.is_empty() {
+        eprintln!("{} orphans kept, --source-of-truth api removes them", plan.orphan.len());
+    }
+
+    if args.dry_run_json {
+        add_remotes(args, palette, log, planned, sources);
+        if remove {
+            planned.extend(plan.orphan.iter().map(|name| PlannedOp::new("prune", name).detail("not a fetched fork")));
+        }
+        return;
+    }
+    print_plan(args, palette, &plan);
+    if args.dry_run {
+        return;
+    }
+
+    if !plan.add.is_empty() {
+        add_remotes(args, palette, log, planned, sources);
+    }
+    if remove && confirm_removal(args, &plan.orphan, "of no fetched fork") {
+        remove_remotes(&repo, &plan.orphan, "sync", palette, log);
+    }
+}
+
+/// Quotes `value` for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Writes the git commands adding the forks of `sources` to `path`, see `--export-script`.
+fn export_script(args: &Args, path: &Path, sources: &[(String, Vec<MinimalRepository>)]) -> io::Result<usize> {
+    let names: Vec<&str> = sources.iter().map(|(source, _)| source.as_str()).collect();
+    let mut script = format!("#!/bin/sh\n# Forks of {} as remotes, generated by rgf\n", names.join(" "));
+    let mut added: HashSet<String> = HashSet::new();
+    for (source, fork) in sources.iter().flat_map(|(source, forks)| forks.iter().map(move |fork| (source, fork))) {
+        let name = fork_remote_name(args, source, fork);
+        if !added.insert(name.clone()) {
+            continue;
+        }
+        let quoted = shell_quote(&name);
+        script.push_str(&format!("\nif ! git remote get-url {} >/dev/null 2>&1; then\n", quoted));
+        script.push_str(&format!("    git remote add {} {}\n", quoted, shell_quote(&fork_url(fork, args))));
+        if let Some(refspec) = &args.refspec {
+            script.push_str(&format!("    git config --replace-all {} {}\n", shell_quote(&format!("remote.{}.fetch", name)), shell_quote(&refspec.replace("{name}", &name))));
+        }
+        if args.no_push {
+            script.push_str(&format!("    git remote set-url --push {} {}\n", quoted, NO_PUSH_URL));
+        }
+        if let Some(description) = remote_description(fork).filter(|_| args.store_description) {
+            script.push_str(&format!("    git config {} {}\n", shell_quote(&format!("remote.{}.description", name)), shell_quote(&description)));
+        }
+        script.push_str("fi\n");
+    }
+
+    fs::write(path, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(added.len())
+}
+
+/// Runs the `--on-add` command for an added remote.
+fn run_hook(command: &str, name: &str, url: &str) -> Result<(), String> {
+    // "rgf" is $0, the remote follows as $1 and $2
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .arg("rgf")
+        .arg(name)
+        .arg(url)
+        .env("RGF_REMOTE_NAME", name)
+        .env("RGF_REMOTE_URL", url)
+        .status()
+        .map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(status.to_string())
+    }
+}
+
+fn add_remotes(args: &Args, palette: &Palette, log: &mut OpLog, planned: &mut Vec<PlannedOp>, sources: &[(String, Vec<MinimalRepository>)]) {
+    let repo = open_repository(args);
+
+    let current_remotes = match repo.remotes() {
+        Ok(remotes) => remotes,
+        Err(e) => {
+            println!("Error: Failed to get remotes: {}", e);
+            exit(1);
+        },
+    };
+    let config_path = match gitconfig::config_path(&repo, args.git_config_scope) {
+        Ok(path) => path,
+        Err(e) => {
+            println!("Error: {}", e);
+            exit(1);
+        },
+    };
+    // git2 does not see the remotes of config.worktree
+    let worktree_remotes = match args.git_config_scope {
+        gitconfig::Scope::Worktree => gitconfig::remote_names(&config_path),
+        gitconfig::Scope::Local => Vec::new(),
+    };
+    // Remote of every url, see --dedupe-remotes
+    let by_url: HashMap<String, String> = if args.dedupe_remotes {
+        current_remotes.iter().flatten()
+            .filter_map(|name| Some((normalize_url(repo.find_remote(name).ok()?.url()?), name.to_string())))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    // All new remotes are written at once, see gitconfig::add_remotes
+    let mut batch: Vec<NewRemote> = Vec::new();
+    let mut added = 0;
+    let mut skipped = 0;
+    let mut failed = 0;
+    let mut hooks_failed = 0;
+    // Per remote lines are dropped with --summary-only and --dry-run-json, their errors go to
+    // stderr instead
+    let quiet = args.summary_only || args.dry_run_json;
+    let item = |line: String| {
+        if !quiet {
+            println!("{}", line);
+        }
+    };
+    let failure = |line: String| {
+        if quiet {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    };
+
+    for (source, fork) in sources.iter().flat_map(|(source, forks)| forks.iter().map(move |fork| (source, fork))) {
+        let remote_name = fork_remote_name(args, source, fork);
+        if remote_name.len() < full_remote_name(args, source, fork).len() {
+            eprintln!("Warning: Name of the remote for {} truncated to {}, see --max-name-length", fork.full_name, remote_name);
+        }
+
+        if current_remotes.iter().any(|r| r.unwrap() == remote_name) || worktree_remotes.contains(&remote_name) || batch.iter().any(|r| r.name == remote_name) {
+            log.log("add", &remote_name, "exists", None);
+            planned.push(PlannedOp::new("skip", &remote_name).detail("exists"));
+            skipped += 1;
+            item(palette.paint(palette.existing, &format!("= {}", remote_name)));
+            continue;
+        }
+
+        if let Some(existing) = by_url.get(&normalize_url(&fork_url(fork, args))) {
+            log.log("add", &remote_name, "exists", Some(existing));
+            planned.push(PlannedOp::new("skip", &remote_name).detail(&format!("same url as {}", existing)));
+            skipped += 1;
+            let kind = if existing.starts_with(REMOTE_PREFIX) { "existing" } else { "existing non-rgf" };
+            item(palette.paint(palette.existing, &format!("= ({}) {}", kind, existing)));
+            continue;
+        }
+
+        if !git2::Remote::is_valid_name(&remote_name) {
+            log.log("add", &remote_name, "failed", Some("invalid remote name"));
+            planned.push(PlannedOp::new("fail", &remote_name).detail("invalid remote name"));
+            failed += 1;
+            failure(palette.paint(palette.failed, &format!("Failed to add remote {}: invalid remote name", remote_name)));
+            continue;
+        }
+
+        if args.dry_run {
+            log.log("add", &remote_name, "planned", None);
+            planned.push(PlannedOp::new("add", &remote_name).url(&fork_url(fork, args)));
+            added += 1;
+            item(palette.paint(palette.planned, &format!("(+) {}", remote_name)));
+            if let Some(command) = &args.on_add {
+                item(palette.paint(palette.planned, &format!("(hook) {} {} {}", command, remote_name, fork_url(fork, args))));
+            }
+            continue;
+        }
+
+        batch.push(NewRemote {
+            fetch: match &args.refspec {
+                Some(refspec) => refspec.replace("{name}", &remote_name),
+                None => gitconfig::default_refspec(&remote_name),
+            },
+            name: remote_name,
+            url: fork_url(fork, args),
+            push_url: args.no_push.then(|| NO_PUSH_URL.to_string()),
+            description: args.store_description.then(|| remote_description(fork)).flatten(),
+        });
+    }
+
+    if !batch.is_empty() {
+        match gitconfig::add_remotes(&config_path, &batch) {
+            Ok(_) => {
+                for remote in &batch {
+                    log.log("add", &remote.name, "added", None);
+                    added += 1;
+                    item(palette.paint(palette.added, &format!("Remote {} added", remote.name)));
+
+                    if let Some(command) = &args.on_add {
+                        match run_hook(command, &remote.name, &remote.url) {
+                            Ok(_) => log.log("on-add", &remote.name, "ok", None),
+                            Err(e) => {
+                                log.log("on-add", &remote.name, "failed", Some(&e));
+                                hooks_failed += 1;
+                                failure(palette.paint(palette.failed, &format!("Hook failed for remote {}: {}", remote.name, e)));
+                            },
+                        }
+                    }
+                }
+            },
+            Err(e) => {
+                for remote in &batch {
+                    log.log("add", &remote.name, "failed", Some(&e.to_string()));
+                    failed += 1;
+                    failure(palette.paint(palette.failed, &format!("Failed to add remote {}: {}", remote.name, e)));
+                }
+            },
+        }
+    }
+
+    // In a dry run the added ones are the ones which would be added
+    let hooks = if args.on_add.is_some() && !args.dry_run { format!(" hook-failed:{}", hooks_failed) } else { String::new() };
+    if !args.dry_run_json {
+        println!("added:{} skipped:{} failed:{}{}{}", added, skipped, failed, hooks, if args.dry_run { " (dry-run)" } else { "" });
+    }
+
+    let title = if args.dry_run { "### Remotes rgf would add" } else { "### Remotes added by rgf" };
+    let counts = vec![added.to_string(), skipped.to_string(), failed.to_string()];
+    ci::append(&format!("{}\n\n{}", title, ci::table(&["added", "skipped", "failed"], &[counts])), !args.no_ci_summary);
+}
+
+/// Statistics of the forks of one owner, see `--owner-stats`.
+#[derive(Serialize, Debug)]
+struct OwnerStats<'a> {
+    owner: &'a str,
+    forks: usize,
+    stars: i64,
+    #[serde(serialize_with = "serialize_rfc3339")]
+    pushed_at: Option<DateTime<Utc>>,
+}
+
+/// Serializes times like `ForkRecord` does.
+fn serialize_rfc3339<S: serde::Serializer>(time: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+    time.map(|t| t.to_rfc3339()).serialize(serializer)
+}
+
+fn owner_stats(args: &Args, sources: &[(String, Vec<MinimalRepository>)]) {
+    let mut owners: BTreeMap<&str, OwnerStats> = BTreeMap::new();
+    for fork in sources.iter().flat_map(|(_, forks)| forks) {
+        let owner = fork.full_name.split('/').next().unwrap_or_default();
+        let stats = owners.entry(owner).or_insert(OwnerStats { owner, forks: 0, stars: 0, pushed_at: None });
+        stats.forks += 1;
+        stats.stars += fork.stargazers_count;
+        stats.pushed_at = stats.pushed_at.max(fork.pushed_at);
+    }
+    let mut owners: Vec<OwnerStats> = owners.into_values().collect();
+    // Stable, so owners with equal stars stay sorted by name
+    owners.sort_by_key(|stats| std::cmp::Reverse(stats.stars));
+
+    match args.format {
+        Format::Text => {
+            let mut out = String::new();
+            for stats in &owners {
+                let pushed_at = stats.pushed_at.map(|t| t.format("%Y-%m-%d").to_string()).unwrap_or("never".to_string());
+                out.push_str(&format!("{} | forks:{} stars:{} last-push:{}\n", stats.owner, stats.forks, stats.stars, pushed_at));
+            }
+            pager::show(&out, !args.no_pager);
+        },
+        Format::Json => println!("{}", to_json(&owners, args)),
+    }
+}
+
+/// Tiers of forks by their last push, in the order of the tiers.
+#[derive(Debug)]
+struct ActivityTiers<'a>(Vec<(&'a str, Vec<ForkRecord<'a>>)>);
+
+// A map keeps its keys sorted, the tiers are serialized in their own order instead
+impl Serialize for ActivityTiers<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(tier, forks)| (tier, forks)))
+    }
+}
+
+/// Prints the forks of `sources` bucketed by `--activity-tiers`, see `--group-by-activity`.
+fn group_by_activity(args: &Args, palette: &Palette, sources: &[(String, Vec<MinimalRepository>)]) {
+    let mut tiers = args.activity_tiers.clone();
+    // Shortest first, so every fork lands in the tightest tier it fits
+    tiers.sort_by_key(|(_, within)| *within);
+
+    let now = Utc::now();
+    let mut grouped: Vec<(&str, Vec<ForkRecord>)> = tiers.iter().map(|(label, _)| (label.as_str(), Vec::new())).collect();
+    grouped.push(("stale", Vec::new()));
+    for (source, fork) in sources.iter().flat_map(|(source, forks)| forks.iter().map(move |fork| (source, fork))) {
+        let tier = fork.pushed_at
+            .and_then(|pushed_at| tiers.iter().position(|(_, within)| pushed_at >= now - *within))
+            .unwrap_or(tiers.len());
+        grouped[tier].1.push(ForkRecord::new(source, fork, None, None, None, None, None));
+    }
+
+    match args.format {
+        Format::Text => {
+            let mut out = String::new();
+            for (tier, forks) in &grouped {
+                let header = if *tier == "stale" { "# stale".to_string() } else { format!("# pushed within {}", tier) };
+                out.push_str(&palette.paint(palette.header, &header));
+                out.push('\n');
+                for fork in forks {
+                    out.push_str(&format!("{} | {}\n", fork.full_name, fork.forks_count));
+                }
+            }
+            pager::show(&out, !args.no_pager);
+        },
+        Format::Json => println!("{}", to_json(&ActivityTiers(grouped), args)),
+    }
+}
+
+/// Runs the checks of `--doctor`, returns whether all of them passed.
+async fn doctor(client: &Client, args: &Args, palette: &Palette) -> bool {
+    let mut passed = true;
+    let mut check = |name: &str, result: Result<String, (String, &str)>| match result {
+        Ok(detail) => println!("{}", palette.paint(palette.added, &format!("[ok]   {}: {}", name, detail))),
+        Err((detail, hint)) => {
+            passed = false;
+            println!("{}", palette.paint(palette.failed, &format!("[fail] {}: {}", name, detail)));
+            println!("       hint: {}", hint);
+        },
+    };
+
+    check("token", match &args.token {
+        Some(token) if !token.is_empty() => Ok("given".to_string()),
+        _ => Err(("none given".to_string(), "set GITHUB_TOKEN or pass --token, without one github allows only 60 api calls per hour")),
+    });
+
+    match client.rate_limit().get().await {
+        Ok(response) => {
+            let rate = response.body.rate;
+            check("api", Ok(if args.token.is_some() { "reachable, token accepted" } else { "reachable" }.to_string()));
+            let reset = Local.timestamp_opt(rate.reset, 0).single().map(|dt| dt.to_rfc2822()).unwrap_or(rate.reset.to_string());
+            check("rate limit", if rate.remaining > 0 {
+                Ok(format!("{}/{} available", rate.remaining, rate.limit))
+            } else {
+                Err((format!("used up until {}", reset), "wait for the reset, or use a token for a higher limit"))
+            });
+        },
+        Err(ClientError::HttpError { status: StatusCode::UNAUTHORIZED, .. }) => {
+            check("api", Err(("reachable, but the token is not accepted".to_string(), "the token is invalid or expired, create a new one at https://github.com/settings/tokens")));
+        },
+        Err(e) => {
+            check("api", Err((format!("not reachable: {}", e), "check the network and proxy settings, for a self-signed certificate see --no-verify-ssl")));
+        },
+    }
+
+    let path = args.repo_path.clone().unwrap_or(PathBuf::from("."));
+    check("git repository", match git2::Repository::discover(&path) {
+        Ok(repo) => Ok(repo.workdir().unwrap_or(repo.path()).display().to_string()),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => {
+            Err(("not inside a git repository".to_string(), "run rgf inside the repository to add the forks to, or pass --repo-path"))
+        },
+        Err(e) => Err((e.message().to_string(), "check that the repository is readable")),
+    });
+
+    passed
+}
+
+/// Current rate limit status as printed by `--rate-limit`.
+async fn rate_limit_line(client: &Client, log: &mut OpLog) -> String {
+    let rate_limit = match client.rate_limit().get().await {
+        Ok(response) => {
+            if response.status == StatusCode::OK {
+                response.body
+            } else {
+                panic!("Response Status not okay: {}", response.status);
+            }
+        },
+        Err(e) => {
+            log.log("rate-limit", "", "failed", Some(&e.to_string()));
+            println!("Error: {}", e);
+            exit(1);
+        }
+    };
+    let dt = match Local.timestamp_opt(rate_limit.rate.reset, 0) {
+        // Some problems, just give the number back as string
+        LocalResult::None => rate_limit.rate.reset.to_string(),
+        LocalResult::Ambiguous(_, _) => rate_limit.rate.reset.to_string(),
+        // Clearly identifiable time. Format as rfc2822
+        LocalResult::Single(dt) => dt.to_rfc2822(),
+    };
+    log.log("rate-limit", "", "ok", Some(&format!("{}/{} available", rate_limit.rate.remaining, rate_limit.rate.limit)));
+    format!("rate-limit:{}/{} available:{} reset-at:{}",
+        rate_limit.rate.used,
+        rate_limit.rate.limit,
+        rate_limit.rate.remaining,
+        dt)
+}
+
+/// Reprints the rate limit every `interval` seconds until Ctrl-C.
+async fn watch_rate_limit(client: &Client, interval: u64, log: &mut OpLog) {
+    // On a terminal the line is updated in place, otherwise one line per update is printed
+    let in_place = stdout().is_terminal();
+    loop {
+        let line = rate_limit_line(client, log).await;
+        if in_place {
+            print!("\r\x1b[2K{}", line);
+            let _ = stdout().flush();
+        } else {
+            println!("{}", line);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_secs(interval)) => (),
+            _ = tokio::signal::ctrl_c() => {
+                if in_place {
+                    println!();
+                }
+                exit(0);
+            },
+        }
+    }
+}
+
+/// Waits for the reset of the rate limit, if it is used up.
+async fn wait_for_quota(client: &Client) {
+    let Ok(response) = client.rate_limit().get().await else { return };
+    let rate = response.body.rate;
+    let wait = rate.reset - Utc::now().timestamp();
+    if rate.remaining == 0 && wait > 0 {
+        eprintln!("Rate limited for the next {} seconds, waiting before the next cycle", wait);
+        tokio::time::sleep(std::time::Duration::from_secs(wait as u64)).await;
+    }
+}
+
+/// Runs rgf with the other arguments every `every` until Ctrl-C, see `--every`.
+async fn run_every(client: &Client, args: &Args, every: Duration) -> ! {
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            println!("Error: Failed to find the rgf executable: {}", e);
+            exit(1);
+        },
+    };
+    // Everything but the options of --every itself
+    let mut child_args = Vec::new();
+    let mut all_args = env::args_os().skip(1);
+    while let Some(arg) = all_args.next() {
+        match arg.to_str() {
+            Some("--every") => {
+                all_args.next();
+            },
+            Some(arg) if arg.starts_with("--every=") || arg == "--only-new" || arg.starts_with("--only-new=") => (),
+            _ => child_args.push(arg),
+        }
+    }
+    let snapshot = if args.only_new {
+        match state::resolve(args.state_dir.as_deref()) {
+            Ok(dir) => Some(dir.join(format!("every-{}.json", std::process::id()))),
+            Err(e) => {
+                println!("Error: {}", e);
+                exit(1);
+            },
+        }
+    } else {
+        None
+    };
+    let stop = || -> ! {
+        if let Some(path) = &snapshot {
+            let _ = fs::remove_file(path);
+        }
+        exit(0);
+    };
+
+    let every = every.to_std().unwrap_or_default();
+    let mut cycle = 0;
+    loop {
+        cycle += 1;
+        let mut command = tokio::process::Command::new(&exe);
+        command.args(&child_args);
+        if let Some(path) = &snapshot {
+            if path.exists() {
+                command.arg("--only-new-since").arg(path);
+            }
+            command.arg("--save-snapshot").arg(path);
+        }
+        if args.verbose {
+            eprintln!("cycle {} at {}", cycle, Local::now().to_rfc2822());
+        }
+
+        // Ctrl-C reaches the running cycle as well, which stops on its own
+        let status = tokio::select! {
+            status = async {
+                wait_for_quota(client).await;
+                command.status().await
+            } => status,
+            _ = tokio::signal::ctrl_c() => stop(),
+        };
+        match status {
+            Ok(status) if !status.success() => eprintln!("Warning: Cycle {} failed with {}", cycle, status),
+            Ok(_) => (),
+            Err(e) => {
+                println!("Error: Failed to run {}: {}", exe.display(), e);
+                exit(1);
+            },
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(every) => (),
+            _ = tokio::signal::ctrl_c() => stop(),
+        }
+    }
+}
+
+/// Prints the remaining retries of `--retry-budget` with `--verbose`.
+fn report_retry_budget(args: &Args, budget: &api::RetryBudget) {
+    if let (true, Some(budget), Some(remaining)) = (args.verbose, args.retry_budget, budget.remaining()) {
+        eprintln!("retry budget: {}/{} left", remaining, budget);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args: Args = Args::parse();
+    if args.dry_run_json {
+        if !args.add && !args.update && !args.sync {
+            println!("Error: --dry-run-json needs --add, --update or --sync");
+            exit(1);
+        }
+        args.dry_run = true;
+    }
+    if args.randomize_order && args.verbose {
+        eprintln!("Random order with --seed {}", args.seed);
+    }
+
+    // Complete the command the way it was invoked, it is often an alias like `rgf`
+    let command_name = env::args_os().next()
+        .and_then(|arg| Path::new(&arg).file_name().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or(env!("CARGO_BIN_NAME").to_string());
+    if let Some(shell) = args.completions {
+        completions::generate(shell, &mut Args::command(), &command_name, &mut io::stdout());
+        return;
+    }
+    if let Some(shell) = args.completions_install {
+        match completions::install(shell, &mut Args::command(), &command_name, args.completions_install_dir.as_deref()) {
+            Ok(path) => {
+                println!("Installed the {} completions to {}", shell, path.display());
+                if let Some(hint) = completions::hint(shell).filter(|_| args.completions_install_dir.is_none()) {
+                    println!("{}", hint);
+                }
+            },
+            Err(e) => {
+                println!("Error: {}", e);
+                exit(1);
+            },
+        }
+        return;
+    }
+
+    if args.version {
+        let (name, version) = (env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        match args.format {
+            Format::Text => println!("{} {}", name, version),
+            Format::Json => println!("{}", to_json(&serde_json::json!({ "name": name, "version": version }), &args)),
+        }
+        return;
+    }
+
+    if args.interactive && !(stdin().is_terminal() && stderr().is_terminal()) {
+        println!("Error: --interactive needs a terminal");
+        exit(1);
+    }
+
+    let from_stdin = args.repository.as_deref() == Some(STDIN_REPOSITORY);
+    let owner_repo = args.repository.as_ref().filter(|_| !from_stdin).map(|r| OwnerRepo::new(r).expect("Invalid repository format: gh standartformat is <owner>/<repo>"));
+
+    // An explicitly requested state dir has to be usable, even if this run does not persist
+    // anything.
+    if args.state_dir.is_some() {
+        if let Err(e) = state::resolve(args.state_dir.as_deref()) {
+            println!("Error: {}", e);
             exit(1);
         }
+    }
+
+    if args.explain {
+        explain(&args);
+        return;
+    }
+
+    let mut log = OpLog::new(args.log_file.clone(), args.log_max_size);
+    // Operations of --add, --update and --sync, see --dry-run-json
+    let mut planned: Vec<PlannedOp> = Vec::new();
+
+    if args.no_verify_ssl {
+        eprintln!("Warning: TLS certificate verification is disabled by --no-verify-ssl, connections are NOT secure");
+    }
+
+    // Read before listing, a broken allowlist or snapshot must not cost any api calls
+    let allowlist = match args.owner_allowlist_file.as_deref().map(read_allowlist) {
+        Some(Ok(allowlist)) => Some(allowlist),
+        Some(Err(e)) => {
+            println!("Error: {}", e);
+            exit(1);
+        },
+        None => None,
     };
 
-    if args.list {
-        for fork in &forks {
-            println!("{} | {}", fork.full_name, fork.forks_count);
+    let snapshot = match args.only_new_since.as_deref().map(read_snapshot) {
+        Some(Ok(snapshot)) => Some(snapshot),
+        Some(Err(e)) => {
+            println!("Error: {}", e);
+            exit(1);
+        },
+        None => None,
+    };
+
+    let palette = Palette::new(if args.no_color { Theme::None } else { args.theme });
+
+    let retry_budget = api::RetryBudget::new(args.retry_budget);
+    let client_options = api::ClientOptions {
+        token: args.token.clone(),
+        api_version: args.api_version.clone(),
+        retry_budget: retry_budget.clone(),
+        follow_redirects: args.follow_renamed_forks || args.prune,
+        accept_invalid_certs: args.no_verify_ssl,
+        log_rate_limit: args.verbose,
+        limit_rate: args.limit_rate,
+    };
+    let client = match api::client(&client_options) {
+        Ok(client) => client,
+        Err(e) => {
+            println!("Error: {}", e);
+            exit(1);
         }
+    };
+
+    if let Some(every) = args.every {
+        run_every(&client, &args, every).await;
     }
 
-    if args.add {
-        let repo = match git2::Repository::discover(".") {
-            Ok(repo) => repo,
-            Err(e) => panic!("Failed to open repository: {}", e),
-        };
+    if args.doctor && !doctor(&client, &args, &palette).await {
+        exit(1);
+    }
 
-        let current_remotes = match repo.remotes() {
-            Ok(remotes) => remotes,
-            Err(e) => panic!("Failed to get remotes: {}", e),
-        };
+    if args.rate_limit {
+        match args.watch {
+            Some(interval) => watch_rate_limit(&client, interval, &mut log).await,
+            None => println!("{}", rate_limit_line(&client, &mut log).await),
+        }
+    }
 
-        for fork in forks {
-            let remote_name = unify_remote_name(&fork.full_name);
+    if args.list_remotes {
+        list_remotes(&args);
+    }
 
-            if current_remotes.iter().any(|r| r.unwrap() == remote_name) {
-                println!("= {}", remote_name);
-                continue;
+    if args.verify_remotes {
+        verify_remotes(&args, &palette, &mut log);
+    }
+
+    if args.update {
+        update_remotes(&client, &args, &palette, &mut log, &mut planned).await;
+    }
+
+    if args.follow_renamed_forks {
+        follow_renamed_forks(&client, &args, &palette, &mut log).await;
+    }
+
+    if args.dedup_by_content {
+        dedup_by_content(&args, &palette, &mut log);
+    }
+
+    if let Some(prefixes) = &args.migrate_prefix {
+        migrate_prefix(&args, &palette, &mut log, &prefixes[0], &prefixes[1]);
+    }
+
+    if let Some(name) = &args.merge_preview {
+        merge_preview(&args, &palette, name);
+    }
+
+    if let Some(forks) = &args.compare_forks {
+        let a = OwnerRepo::new(&forks[0]).expect("Invalid repository format: gh standartformat is <owner>/<repo>");
+        let b = OwnerRepo::new(&forks[1]).expect("Invalid repository format: gh standartformat is <owner>/<repo>");
+        compare_forks(&client, &args, &a, &b, &palette, &mut log).await;
+    }
+
+    // These modes do not need the forks
+    if owner_repo.is_none() && args.org.is_none() && !from_stdin {
+        print_dry_run_plan(&args, &planned);
+        report_retry_budget(&args, &retry_budget);
+        return;
+    }
+
+    let owner_repo = match owner_repo {
+        Some(owner_repo) if args.root => Some(root_repository(&client, owner_repo, &args, &mut log).await),
+        owner_repo => owner_repo,
+    };
+
+    // Whether every source was listed, see --sync
+    let (mut sources, all_sources_listed) = match (&args.org, &owner_repo) {
+        _ if from_stdin => list_stdin_forks(&client, &args, &mut log).await,
+        (Some(org), _) => list_org_forks(&client, org, &args, &mut log).await,
+        (None, Some(owner_repo)) => match list_forks(&client, &owner_repo.owner, &owner_repo.repo, &args).await {
+            Ok(forks) => (vec![(format!("{}/{}", owner_repo.owner, owner_repo.repo), forks)], true),
+            Err(e) => {
+                log.log("list-forks", args.repository.as_deref().unwrap(), "failed", Some(&e.to_string()));
+                api_error(&e, &args);
+                exit(1);
             }
+        },
+        // clap requires one of both
+        (None, None) => unreachable!(),
+    };
 
-            if args.dry_run {
-                println!("(+) {}", remote_name);
-                continue;
-            } else {
-                match repo.remote(&remote_name, &fork.clone_url) {
-                    Ok(_) => println!("Remote {} added", remote_name),
-                    Err(e) => println!("Failed to add remote {}: {}", remote_name, e),
+    for (source, forks) in &sources {
+        log.log("list-forks", source, "ok", Some(&format!("{} forks", forks.len())));
+    }
+    // A full last page may have more after it, see list_forks
+    let complete_listing = all_sources_listed && args.page <= 1 && sources.iter()
+        .all(|(_, forks)| forks.len() < args.per_page.min(100) as usize * args.max_pages as usize);
+
+    if let Some(path) = &args.save_snapshot {
+        let names: Vec<serde_json::Value> = sources.iter()
+            .flat_map(|(_, forks)| forks)
+            .map(|fork| serde_json::json!({ "full_name": fork.full_name }))
+            .collect();
+        if let Err(e) = fs::write(path, to_json(&names, &args)) {
+            eprintln!("Warning: Failed to write the snapshot {}: {}", path.display(), e);
+        }
+    }
+
+    let enriched = if args.enrich {
+        enrich_forks(&client, &mut sources, &args, &mut log).await
+    } else {
+        HashMap::new()
+    };
+
+    // Remotes of all fetched forks, filtered or not, to tell orphans from filtered forks
+    let fetched: HashSet<String> = sources.iter()
+        .flat_map(|(source, forks)| forks.iter().map(|fork| fork_remote_name(&args, source, fork)))
+        .collect();
+
+    for (_, forks) in sources.iter_mut() {
+        filter_forks(forks, &args, allowlist.as_ref());
+        // Before the search, so equally good matches are in stable order as well
+        if args.stable || args.format == Format::Json || args.sort_dir.is_some() || matches!(args.sort, ForkSort::Name | ForkSort::Forks) {
+            args.sort.sort_stable(forks, args.sort_dir.unwrap_or(args.sort.default_dir()));
+        }
+        if let Some(query) = &args.search {
+            search_forks(forks, query);
+        }
+    }
+
+    // --check prints nothing unless --verbose
+    let summaries = args.verbose || !args.check;
+
+    if let Some(snapshot) = &snapshot {
+        let mut known = 0;
+        for (_, forks) in sources.iter_mut() {
+            let before = forks.len();
+            forks.retain(|fork| !snapshot.contains(&fork.full_name.to_lowercase()));
+            known += before - forks.len();
+        }
+        if summaries {
+            eprintln!("{} forks already in the snapshot filtered out", known);
+        }
+    }
+
+    if let Some(min_forks_count) = args.min_forks_count {
+        let mut dropped = 0;
+        for (_, forks) in sources.iter_mut() {
+            let before = forks.len();
+            forks.retain(|fork| fork.forks_count >= min_forks_count);
+            dropped += before - forks.len();
+        }
+        if summaries {
+            eprintln!("{} forks with less than {} forks filtered out", dropped, min_forks_count);
+        }
+    }
+
+    let contributors = if args.with_contributors() {
+        contributor_counts(&client, &sources, &args, &mut log).await
+    } else {
+        HashMap::new()
+    };
+
+    let last_commits = if args.with_last_commit() {
+        last_commits(&client, &sources, &args, &mut log).await
+    } else {
+        HashMap::new()
+    };
+
+    let upstream_prs = if args.with_upstream_prs() {
+        upstream_pr_counts(&client, &sources, &args, &mut log).await
+    } else {
+        HashMap::new()
+    };
+
+    let topics = if args.with_topics() {
+        fork_topics(&client, &sources, &args, &mut log).await
+    } else {
+        HashMap::new()
+    };
+
+    // --enrich has looked up the forks already
+    let relationships = if !args.with_relationships() {
+        HashMap::new()
+    } else if args.enrich {
+        enriched
+    } else {
+        fork_relationships(&client, &sources, &args, &mut log).await
+    };
+
+    if let Some(min_upstream_prs) = args.min_upstream_prs {
+        let mut dropped = 0;
+        for (_, forks) in sources.iter_mut() {
+            let before = forks.len();
+            forks.retain(|fork| upstream_prs.get(&fork.full_name).is_some_and(|count| *count >= min_upstream_prs));
+            dropped += before - forks.len();
+        }
+        if summaries {
+            eprintln!("{} forks with less than {} upstream pull requests filtered out", dropped, min_upstream_prs);
+        }
+    }
+
+    if let Some(min_contributors) = args.min_contributors {
+        let mut dropped = 0;
+        for (_, forks) in sources.iter_mut() {
+            let before = forks.len();
+            forks.retain(|fork| contributors.get(&fork.full_name).is_some_and(|count| *count >= min_contributors));
+            dropped += before - forks.len();
+        }
+        if summaries {
+            eprintln!("{} forks with less than {} contributors filtered out", dropped, min_contributors);
+        }
+    }
+
+    let compare = args.with_divergences();
+    let upstreams = if (args.list && args.include_source) || compare || args.highlight_shared_topics {
+        source_repos(&client, &sources, &mut log).await
+    } else {
+        HashMap::new()
+    };
+
+    let divergences = if compare {
+        divergences(&client, &sources, &upstreams, &args, &mut log).await
+    } else {
+        HashMap::new()
+    };
+
+    if args.skip_forks_behind {
+        let mut skipped = 0;
+        for (_, forks) in sources.iter_mut() {
+            let before = forks.len();
+            forks.retain(|fork| divergences.get(&fork.full_name).is_none_or(|divergence| divergence.ahead_by > 0));
+            skipped += before - forks.len();
+        }
+        if summaries {
+            eprintln!("{} forks without commits ahead of their source skipped", skipped);
+        }
+    }
+
+    if let Some(min_ahead) = args.add_if_ahead {
+        let mut dropped = 0;
+        for (_, forks) in sources.iter_mut() {
+            let before = forks.len();
+            forks.retain(|fork| divergences.get(&fork.full_name).is_some_and(|divergence| divergence.ahead_by >= min_ahead));
+            dropped += before - forks.len();
+        }
+        if summaries {
+            eprintln!("{} forks less than {} commits ahead filtered out", dropped, min_ahead);
+        }
+    }
+
+    if let Some(class) = args.class {
+        let mut dropped = 0;
+        for (_, forks) in sources.iter_mut() {
+            let before = forks.len();
+            forks.retain(|fork| divergences.get(&fork.full_name).is_some_and(|divergence| divergence.class == Some(class)));
+            dropped += before - forks.len();
+        }
+        if summaries {
+            eprintln!("{} forks not {} filtered out", dropped, class);
+        }
+    }
+
+    if args.only_verified {
+        let mut dropped = 0;
+        for (_, forks) in sources.iter_mut() {
+            let before = forks.len();
+            forks.retain(|fork| divergences.get(&fork.full_name).is_some_and(Divergence::verified));
+            dropped += before - forks.len();
+        }
+        if summaries {
+            eprintln!("{} forks with unverified commits filtered out", dropped);
+        }
+    }
+
+    if args.check {
+        let matching: Vec<&MinimalRepository> = sources.iter().flat_map(|(_, forks)| forks).collect();
+        if args.verbose {
+            println!("{} matching forks", matching.len());
+            for fork in &matching {
+                println!("{}", fork.full_name);
+            }
+        }
+        exit(if matching.is_empty() { EXIT_NO_MATCH } else { 0 });
+    }
+
+    if args.list {
+        let listed = |fork: &&MinimalRepository| !args.hide_zero_forks || fork.forks_count > 0;
+        if args.hide_zero_forks {
+            let hidden = sources.iter().flat_map(|(_, forks)| forks).filter(|fork| !listed(fork)).count();
+            eprintln!("{} forks without forks hidden", hidden);
+        }
+        match args.format {
+            _ if args.urls_only => {
+                let mut out = String::new();
+                for fork in sources.iter().flat_map(|(_, forks)| forks).filter(listed) {
+                    out.push_str(&fork_url(fork, &args));
+                    out.push('\n');
+                }
+                pager::show(&out, !args.no_pager);
+            },
+            Format::Text if args.output_template_file.is_some() => {
+                let template = args.output_template_file.as_ref().expect("Checked by the match");
+                let listed_forks: Vec<(&String, &MinimalRepository)> = sources.iter()
+                    .flat_map(|(source, forks)| forks.iter().filter(listed).map(move |fork| (source, fork)))
+                    .collect();
+                let names: Vec<&str> = sources.iter().map(|(source, _)| source.as_str()).collect();
+                let outer = |field: &str| match field {
+                    "count" => listed_forks.len().to_string(),
+                    _ => names.join(", "),
+                };
+                let forks = listed_forks.iter().map(|(source, fork)| {
+                    let cells = Cells::fork(fork, contributors.get(&fork.full_name).copied(), last_commits.get(&fork.full_name), upstream_prs.get(&fork.full_name).copied(), topics.get(&fork.full_name), relationships.get(&fork.full_name));
+                    let args = &args;
+                    move |field: &str| match field {
+                        "source" => source.to_string(),
+                        "url" => fork.html_url.clone(),
+                        _ => Column::from_str(field, false).map(|column| cells.get(column, args)).unwrap_or_default(),
+                    }
+                });
+                pager::show(&template.render(&outer, forks), !args.no_pager);
+            },
+            Format::Text => {
+                let mut columns = vec![Column::Name, Column::Forks];
+                if args.with_contributors() {
+                    columns.push(Column::Contributors);
+                }
+                if args.with_last_commit() {
+                    columns.push(Column::LastCommit);
+                }
+                if args.with_upstream_prs() {
+                    columns.push(Column::UpstreamPrs);
+                }
+                if args.with_topics() {
+                    columns.push(Column::Topics);
+                }
+                if args.with_relationships() {
+                    columns.push(Column::Relationship);
+                }
+                let columns = args.columns.clone().unwrap_or(columns);
+
+                // Source headers go between the rows, so the rows are aligned over all sources
+                let mut rows: Vec<Vec<String>> = Vec::new();
+                // Painted after aligning, escape sequences would count as width
+                let mut highlighted: HashSet<usize> = HashSet::new();
+                let mut headers: Vec<(usize, &str)> = Vec::new();
+                // Appended after aligning as well, see --compare
+                let mut compared: HashMap<usize, Option<&Divergence>> = HashMap::new();
+                if args.columns.is_some() {
+                    rows.push(columns.iter().map(|column| column.to_possible_value().unwrap().get_name().to_string()).collect());
+                }
+                for (source, forks) in &sources {
+                    if args.org.is_some() || from_stdin {
+                        headers.push((rows.len(), source));
+                    }
+                    if let Some(upstream) = upstreams.get(source).filter(|_| args.include_source) {
+                        let cells = Cells::source(upstream);
+                        let mut row: Vec<String> = columns.iter().map(|column| cells.get(*column, &args)).collect();
+                        if let Some(last) = row.last_mut() {
+                            last.push_str(" (source)");
+                        }
+                        rows.push(row);
+                    }
+                    let source_topics = upstreams.get(source).map(|upstream| &upstream.topics);
+                    for fork in forks.iter().filter(listed) {
+                        let shares_topic = topics.get(&fork.full_name)
+                            .zip(source_topics)
+                            .is_some_and(|(Topics(topics), source_topics)| topics.iter().any(|topic| source_topics.contains(topic)));
+                        if args.highlight_shared_topics && shares_topic {
+                            highlighted.insert(rows.len());
+                        }
+                        if args.compare {
+                            compared.insert(rows.len(), divergences.get(&fork.full_name));
+                        }
+                        let cells = Cells::fork(fork, contributors.get(&fork.full_name).copied(), last_commits.get(&fork.full_name), upstream_prs.get(&fork.full_name).copied(), topics.get(&fork.full_name), relationships.get(&fork.full_name));
+                        rows.push(columns.iter().map(|column| cells.get(*column, &args)).collect());
+                    }
+                }
+
+                let mut out = String::new();
+                let mut headers = headers.into_iter().peekable();
+                for (i, line) in render_rows(&rows, args.align()).into_iter().enumerate() {
+                    while let Some((_, source)) = headers.next_if(|(at, _)| *at == i) {
+                        out.push_str(&palette.paint(palette.header, &format!("# {}", source)));
+                        out.push('\n');
+                    }
+                    if highlighted.contains(&i) {
+                        out.push_str(&palette.paint(palette.highlight, &line));
+                    } else {
+                        out.push_str(&line);
+                    }
+                    match compared.get(&i) {
+                        Some(Some(divergence)) => {
+                            out.push_str(&format!(" | {}", divergence.summary(&palette)));
+                            for commit in divergence.newest_commits(args.max_commits) {
+                                out.push_str(&format!("\n    {} {}", &commit.sha[..commit.sha.len().min(7)], commit.subject));
+                            }
+                        },
+                        Some(None) => out.push_str(" | ?"),
+                        None => (),
+                    }
+                    out.push('\n');
+                }
+                // Sources without any listed fork
+                for (_, source) in headers {
+                    out.push_str(&palette.paint(palette.header, &format!("# {}", source)));
+                    out.push('\n');
+                }
+                pager::show(&out, !args.no_pager);
+            },
+            Format::Json => {
+                let records: Vec<ForkRecord> = sources.iter()
+                    .flat_map(|(source, forks)| {
+                        let upstream = upstreams.get(source).filter(|_| args.include_source).map(ForkRecord::source);
+                        let forks = forks.iter().filter(listed).map(|fork| ForkRecord {
+                            divergence: divergences.get(&fork.full_name),
+                            ..ForkRecord::new(source, fork, contributors.get(&fork.full_name).copied(), last_commits.get(&fork.full_name), upstream_prs.get(&fork.full_name).copied(), topics.get(&fork.full_name), relationships.get(&fork.full_name))
+                        });
+                        upstream.into_iter().chain(forks)
+                    })
+                    .collect();
+                println!("{}", to_json(&records, &args));
+            },
+        }
+
+        let with_contributors = args.with_contributors();
+        let mut header = vec!["source", "fork", "forks"];
+        if with_contributors {
+            header.push("contributors");
+        }
+        if args.with_last_commit() {
+            header.push("last commit");
+        }
+        if args.with_upstream_prs() {
+            header.push("upstream prs");
+        }
+        if args.with_topics() {
+            header.push("topics");
+        }
+        if args.with_relationships() {
+            header.push("relationship");
+        }
+        let rows: Vec<Vec<String>> = sources.iter()
+            .flat_map(|(source, forks)| forks.iter().map(move |fork| (source, fork)))
+            .filter(|(_, fork)| listed(fork))
+            .map(|(source, fork)| {
+                let mut row = vec![source.clone(), fork.full_name.clone(), fork.forks_count.to_string()];
+                if with_contributors {
+                    row.push(contributors.get(&fork.full_name).map(|count| count.to_string()).unwrap_or("?".to_string()));
+                }
+                if args.with_last_commit() {
+                    row.push(last_commits.get(&fork.full_name).map(|commit| commit.to_string()).unwrap_or("?".to_string()));
+                }
+                if args.with_upstream_prs() {
+                    row.push(upstream_prs.get(&fork.full_name).map(|count| count.to_string()).unwrap_or("?".to_string()));
+                }
+                if args.with_topics() {
+                    row.push(topics.get(&fork.full_name).map(|topics| topics.to_string()).unwrap_or("?".to_string()));
+                }
+                if args.with_relationships() {
+                    row.push(relationships.get(&fork.full_name).map(|relationship| relationship.to_string()).unwrap_or("?".to_string()));
+                }
+                row
+            })
+            .collect();
+        ci::append(&format!("### Forks found by rgf\n\n{}", ci::table(&header, &rows)), !args.no_ci_summary);
+    }
+
+    if args.owner_stats {
+        owner_stats(&args, &sources);
+    }
+
+    if args.group_by_activity {
+        group_by_activity(&args, &palette, &sources);
+    }
+
+    if let Some(count_by) = args.count_by {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for fork in sources.iter().flat_map(|(_, forks)| forks) {
+            *counts.entry(count_by.bucket(fork).unwrap_or("unknown")).or_default() += 1;
+        }
+        match args.format {
+            Format::Text => {
+                let mut buckets: Vec<(&str, usize)> = counts.into_iter().collect();
+                // Stable, so equal counts stay sorted by name
+                buckets.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+                for (bucket, count) in buckets {
+                    println!("{} | {}", bucket, count);
                 }
+            },
+            Format::Json => println!("{}", to_json(&counts, &args)),
+        }
+    }
+
+    if args.stats {
+        let selected: Vec<&MinimalRepository> = sources.iter().flat_map(|(_, forks)| forks).collect();
+        let size: i64 = selected.iter().map(|fork| fork.size).sum();
+        println!("forks:{} size:{}KB", selected.len(), size);
+    }
+
+    if args.interactive && !select_forks(&mut sources) {
+        println!("Aborted, no remotes added");
+        return;
+    }
+
+    if let Some(path) = &args.export_script {
+        match export_script(&args, path, &sources) {
+            Ok(count) => {
+                log.log("export-script", &path.display().to_string(), "ok", Some(&format!("{} remotes", count)));
+                println!("Wrote {} remotes to {}", count, path.display());
+            },
+            Err(e) => {
+                log.log("export-script", &path.display().to_string(), "failed", Some(&e.to_string()));
+                println!("Error: Failed to write {}: {}", path.display(), e);
+                exit(1);
+            },
+        }
+    }
+
+    if args.plan {
+        plan(&args, &palette, &sources, &fetched);
+    }
+
+    if args.add {
+        add_remotes(&args, &palette, &mut log, &mut planned, &sources);
+    }
+
+    if args.sync {
+        sync(&args, &palette, &mut log, &mut planned, &sources, &fetched, complete_listing);
+    }
+
+    print_dry_run_plan(&args, &planned);
+    report_retry_budget(&args, &retry_budget);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minimal_repository(full_name: &str, stargazers_count: i64) -> MinimalRepository {
+        serde_json::from_value(serde_json::json!({
+            "full_name": full_name,
+            "stargazers_count": stargazers_count,
+        }))
+        .unwrap()
+    }
+
+    fn full_repository(full_name: &str) -> FullRepository {
+        serde_json::from_value(serde_json::json!({
+            "full_name": full_name,
+            "clone_url": format!("https://github.com/{}.git", full_name),
+            "ssh_url": format!("git@github.com:{}.git", full_name),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn discover_repository_outside_of_a_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let e = discover_repository(Some(dir.path())).err().unwrap();
+        assert_eq!(e, (EXIT_NOT_IN_REPO, format!("{} is not inside a git repository", dir.path().display())));
+    }
+
+    #[test]
+    fn not_in_repository_of_the_current_directory() {
+        let e = not_in_repository(None);
+        assert_eq!(e, (EXIT_NOT_IN_REPO, "Not inside a git repository; run from within one or pass --repo-path".to_string()));
+    }
+
+    #[test]
+    fn discover_repository_from_a_subdirectory() {
+        let dir = tempfile::tempdir().unwrap();
+        git2::Repository::init(dir.path()).unwrap();
+        let sub = dir.path().join("src/deep");
+        std::fs::create_dir_all(&sub).unwrap();
+        let repo = discover_repository(Some(&sub)).ok().unwrap();
+        assert_eq!(repo.workdir().unwrap().canonicalize().unwrap(), dir.path().canonicalize().unwrap());
+    }
+
+    #[test]
+    fn fork_sort_github_sort_of_every_value() {
+        let expected = [
+            ("newest", ForkSort::Newest, "newest"),
+            ("oldest", ForkSort::Oldest, "oldest"),
+            ("stargazers", ForkSort::Stargazers, "stargazers"),
+            ("watchers", ForkSort::Watchers, "watchers"),
+            // Sorted by rgf, any order of github will do
+            ("name", ForkSort::Name, "newest"),
+            ("forks", ForkSort::Forks, "newest"),
+        ];
+        assert_eq!(ForkSort::value_variants().len(), expected.len());
+        for (value, variant, api) in expected {
+            let sort = ForkSort::from_str(value, false).unwrap();
+            assert_eq!(sort, variant);
+            assert_eq!(sort.github_sort().to_string(), api, "{}", value);
+        }
+    }
+
+    #[test]
+    fn sort_stable_orders_equal_keys_by_name() {
+        let names = |forks: &[MinimalRepository]| forks.iter().map(|fork| fork.full_name.clone()).collect::<Vec<_>>();
+        let forks = vec![
+            minimal_repository("c/repo", 0),
+            minimal_repository("top/repo", 5),
+            minimal_repository("a/repo", 0),
+            minimal_repository("b/repo", 0),
+        ];
+        let mut reversed = forks.clone();
+        reversed.reverse();
+
+        let mut sorted = forks.clone();
+        ForkSort::Stargazers.sort_stable(&mut sorted, SortDir::Desc);
+        assert_eq!(names(&sorted), ["top/repo", "a/repo", "b/repo", "c/repo"]);
+        // The order of github does not matter
+        ForkSort::Stargazers.sort_stable(&mut reversed, SortDir::Desc);
+        assert_eq!(names(&reversed), names(&sorted));
+        // Nor does the direction for the ties
+        ForkSort::Stargazers.sort_stable(&mut sorted, SortDir::Asc);
+        assert_eq!(names(&sorted), ["a/repo", "b/repo", "c/repo", "top/repo"]);
+    }
+
+    #[test]
+    fn remote_full_name_inverts_unify_remote_name() {
+        for full_name in ["owner/repo", "my-org/my.repo", "o-w-n/r.e-p.o", "owner/repo.js", "owner/my_repo"] {
+            let name = unify_remote_name(full_name);
+            for url in [format!("https://github.com/{}.git", full_name), format!("git@github.com:{}.git", full_name), "/srv/git/mirror.git".to_string()] {
+                let resolved = remote_full_name(&name, &url);
+                assert_eq!(resolved.as_deref(), Some(full_name), "{} {}", name, url);
+                assert_eq!(unify_remote_name(&resolved.unwrap()), name);
             }
         }
     }
 
+    #[test]
+    fn remote_full_name_takes_the_url_for_prefixed_names() {
+        let name = format!("{}__{}", unify_remote_name("google/battery-historian"), "ntc-stefan_battery-historian");
+        let url = "https://github.com/ntc-stefan/battery-historian.git";
+        assert_eq!(remote_full_name(&name, url).as_deref(), Some("ntc-stefan/battery-historian"));
+        // Without a github url the name is ambiguous
+        assert_eq!(remote_full_name(&name, "/srv/git/mirror.git"), None);
+    }
+
+    #[test]
+    fn remote_full_name_of_other_remotes() {
+        assert_eq!(remote_full_name("origin", "/srv/git/mirror.git"), None);
+        assert_eq!(remote_full_name("rgf__owner", "/srv/git/mirror.git"), None);
+    }
+
+    #[test]
+    fn renamed_remote_of_a_renamed_owner() {
+        let fork = full_repository("new-owner/repo");
+        let renamed = renamed_remote("rgf__old-owner_repo", "https://github.com/old-owner/repo.git", "old-owner/repo", &fork, 200);
+        assert_eq!(renamed, ("rgf__new-owner_repo".to_string(), "https://github.com/new-owner/repo.git".to_string()));
+    }
+
+    #[test]
+    fn renamed_remote_of_a_renamed_repository() {
+        let fork = full_repository("owner/new.repo");
+        let renamed = renamed_remote("rgf__owner_old-repo", "git@github.com:owner/old-repo.git", "owner/old-repo", &fork, 200);
+        // The protocol of the remote is kept
+        assert_eq!(renamed, ("rgf__owner_new.repo".to_string(), "git@github.com:owner/new.repo.git".to_string()));
+    }
+
+    #[test]
+    fn renamed_remote_keeps_the_prefix_per_source() {
+        let fork = full_repository("owner/new-repo");
+        let (name, _) = renamed_remote("rgf__google_battery-historian__owner_old-repo", "https://github.com/owner/old-repo.git", "owner/old-repo", &fork, 200);
+        assert_eq!(name, "rgf__google_battery-historian__owner_new-repo");
+    }
+
+    #[test]
+    fn move_remote_to_a_taken_name_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        repo.remote("rgf__owner_old-repo", "https://github.com/owner/old-repo.git").unwrap();
+        repo.remote("rgf__owner_new-repo", "https://github.com/other/new-repo.git").unwrap();
+
+        let fork = full_repository("owner/new-repo");
+        let (new_name, new_url) = renamed_remote("rgf__owner_old-repo", "https://github.com/owner/old-repo.git", "owner/old-repo", &fork, 200);
+        let e = move_remote(&repo, "rgf__owner_old-repo", &new_name, &new_url).unwrap_err();
+        assert_eq!(e.message(), "remote rgf__owner_new-repo already exists");
+
+        assert_eq!(repo.find_remote("rgf__owner_old-repo").unwrap().url(), Some("https://github.com/owner/old-repo.git"));
+        assert_eq!(repo.find_remote("rgf__owner_new-repo").unwrap().url(), Some("https://github.com/other/new-repo.git"));
+    }
+
+    #[test]
+    fn move_remote_renames_and_repoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        repo.remote("rgf__owner_old-repo", "https://github.com/owner/old-repo.git").unwrap();
+
+        move_remote(&repo, "rgf__owner_old-repo", "rgf__owner_new-repo", "https://github.com/owner/new-repo.git").unwrap();
+        assert!(repo.find_remote("rgf__owner_old-repo").is_err());
+        assert_eq!(repo.find_remote("rgf__owner_new-repo").unwrap().url(), Some("https://github.com/owner/new-repo.git"));
+    }
+
+    #[test]
+    fn canonical_url_strips_trailing_slashes() {
+        assert_eq!(canonical_url("https://github.com/owner/repo/"), "https://github.com/owner/repo.git");
+        assert_eq!(canonical_url("https://github.com/owner/repo.git//"), "https://github.com/owner/repo.git");
+    }
+
+    #[test]
+    fn canonical_url_has_a_single_git_suffix() {
+        assert_eq!(canonical_url("https://github.com/owner/repo"), "https://github.com/owner/repo.git");
+        assert_eq!(canonical_url("https://github.com/owner/repo.git"), "https://github.com/owner/repo.git");
+        assert_eq!(canonical_url("https://github.com/owner/repo.git.git"), "https://github.com/owner/repo.git");
+    }
+
+    #[test]
+    fn canonical_url_lowercases_only_the_host() {
+        assert_eq!(canonical_url("https://GitHub.COM/Owner/Repo"), "https://github.com/Owner/Repo.git");
+        assert_eq!(canonical_url("https://User@GitHub.com/Owner/Repo"), "https://User@github.com/Owner/Repo.git");
+    }
+
+    #[test]
+    fn canonical_url_of_the_scp_form() {
+        assert_eq!(canonical_url("git@GitHub.com:Owner/Repo"), "git@github.com:Owner/Repo.git");
+        assert_eq!(canonical_url("git@github.com:owner/repo.git/"), "git@github.com:owner/repo.git");
+    }
+
+    #[test]
+    fn normalize_url_makes_https_and_ssh_urls_equal() {
+        let expected = "github.com/owner/repo";
+        for url in [
+            "https://github.com/owner/repo",
+            "https://github.com/owner/repo/",
+            "https://github.com/owner/repo.git",
+            "https://GitHub.com/Owner/Repo.git",
+            "https://user@github.com/owner/repo",
+            "ssh://git@github.com/owner/repo.git",
+            "git@github.com:owner/repo",
+            "git@GITHUB.com:Owner/Repo.git",
+        ] {
+            assert_eq!(normalize_url(url), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn truncate_remote_name_keeps_names_up_to_the_limit() {
+        assert_eq!(truncate_remote_name("rgf__owner_repo".to_string(), 15), "rgf__owner_repo");
+        assert_eq!(truncate_remote_name("rgf__owner_repo".to_string(), 200), "rgf__owner_repo");
+    }
+
+    #[test]
+    fn truncate_remote_name_cuts_longer_names_with_a_hash() {
+        let name = "rgf__owner_a-rather-long-repository-name";
+        let truncated = truncate_remote_name(name.to_string(), 30);
+        assert_eq!(truncated.len(), 30);
+        // `-` and 8 hex digits
+        let (kept, suffix) = truncated.split_at(21);
+        assert!(name.starts_with(kept), "{}", truncated);
+        assert!(suffix.starts_with('-') && suffix[1..].chars().all(|c| c.is_ascii_hexdigit()), "{}", truncated);
+        // The same in every run, it names the remote
+        assert_eq!(truncate_remote_name(name.to_string(), 30), truncated);
+    }
+
+    #[test]
+    fn truncate_remote_name_tells_names_with_the_same_prefix_apart() {
+        let a = truncate_remote_name("rgf__owner_a-rather-long-repository-name-one".to_string(), 30);
+        let b = truncate_remote_name("rgf__owner_a-rather-long-repository-name-two".to_string(), 30);
+        assert_eq!(a[..21], b[..21]);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn truncate_remote_name_never_splits_a_character() {
+        // The cut at byte 11 falls into the second `ä`
+        let name = "rgf__aääääääääää".to_string();
+        let truncated = truncate_remote_name(name.clone(), 20);
+        assert!(truncated.len() <= 20, "{}", truncated);
+        assert!(name.starts_with(&truncated[..truncated.len() - 9]), "{}", truncated);
+        for max_length in 10..name.len() as u16 {
+            assert!(truncate_remote_name(name.clone(), max_length).len() <= max_length as usize);
+        }
+    }
+
+    #[test]
+    fn processing_order_with_a_seed() {
+        let order = |argv: &[&str]| {
+            let args = Args::parse_from(["rgf", "owner/repo", "--list"].iter().chain(argv));
+            let mut items: Vec<u32> = (0..50).collect();
+            processing_order(&mut items, &args);
+            items
+        };
+        let api: Vec<u32> = (0..50).collect();
+        assert_eq!(order(&[]), api);
+        let shuffled = order(&["--randomize-order", "--seed", "7"]);
+        assert_ne!(shuffled, api);
+        assert_eq!(order(&["--randomize-order", "--seed", "7"]), shuffled);
+        // Drawn without --seed, still shuffled
+        assert_ne!(order(&["--randomize-order"]), api);
+    }
+
+    #[test]
+    fn divergence_summary_and_newest_commits() {
+        let commit = |sha: &str, subject: &str| UniqueCommit { sha: sha.to_string(), subject: subject.to_string(), verified: false };
+        let divergence = Divergence {
+            ahead_by: 3,
+            behind_by: 12,
+            class: Some(ForkClass::Diverged),
+            commits: vec![commit("a1", "Oldest"), commit("b2", "Middle"), commit("c3", "Newest")],
+        };
+        assert_eq!(divergence.summary(&Palette::new(Theme::None)), "+3/-12");
+        assert_eq!(divergence.summary(&Palette::new(Theme::Dark)), "\x1b[1;32m+3\x1b[0m/\x1b[31m-12\x1b[0m");
+        let subjects = |max| divergence.newest_commits(max).map(|commit| commit.subject.as_str()).collect::<Vec<_>>();
+        assert_eq!(subjects(2), ["Newest", "Middle"]);
+        assert_eq!(subjects(10), ["Newest", "Middle", "Oldest"]);
+        assert!(subjects(0).is_empty());
+    }
 }
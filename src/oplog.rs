@@ -0,0 +1,101 @@
+// Structured log of all operations, one JSON object per line.
+//
+// Meant for scheduled runs: stdout shows what happened, the log file keeps it for later.
+
+use chrono::Utc;
+use serde_json::json;
+use std::fs::{
+    self,
+    File,
+    OpenOptions,
+};
+use std::io::{
+    self,
+    Write,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+/// Number of rotated files kept next to the log file (`<log>.1` is the newest).
+const KEEP_ROTATED: u32 = 3;
+
+pub struct OpLog {
+    path: Option<PathBuf>,
+    max_size: u64,
+    file: Option<File>,
+    size: u64,
+}
+
+impl OpLog {
+    /// Without a path all records are dropped.
+    pub fn new(path: Option<PathBuf>, max_size: u64) -> OpLog {
+        OpLog {
+            path,
+            max_size,
+            file: None,
+            size: 0,
+        }
+    }
+
+    /// Appends a record of `op` on `target`. `outcome` is a short keyword (`added`, `failed`,
+    /// ...), `detail` an optional message like the error.
+    pub fn log(&mut self, op: &str, target: &str, outcome: &str, detail: Option<&str>) {
+        let Some(path) = self.path.clone() else { return };
+
+        let mut line = json!({
+            "time": Utc::now().to_rfc3339(),
+            "op": op,
+            "target": target,
+            "outcome": outcome,
+            "detail": detail,
+        })
+        .to_string();
+        line.push('\n');
+
+        // A broken log must not break the run, warn once and stop logging.
+        if let Err(e) = self.write(&path, line.as_bytes()) {
+            eprintln!("Warning: Failed to write log file {}: {}", path.display(), e);
+            self.path = None;
+        }
+    }
+
+    fn write(&mut self, path: &Path, line: &[u8]) -> io::Result<()> {
+        if self.file.is_none() {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.file = None;
+            rotate(path)?;
+            self.file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+            self.size = 0;
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(line)?;
+            self.size += line.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+/// Shifts `<log>.N` to `<log>.N+1`, dropping the oldest, and moves the log itself to `<log>.1`.
+fn rotate(path: &Path) -> io::Result<()> {
+    let rotated = |n: u32| {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    };
+
+    for n in (1..KEEP_ROTATED).rev() {
+        let from = rotated(n);
+        if from.exists() {
+            fs::rename(&from, rotated(n + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))
+}
@@ -0,0 +1,50 @@
+// Shows long listings through a pager, like git does.
+
+use std::env;
+use std::io::{
+    stdout,
+    IsTerminal,
+    Write,
+};
+use std::process::{
+    Command,
+    Stdio,
+};
+
+/// Pager used if `PAGER` is not set. `-R` passes the colors through.
+const DEFAULT_PAGER: &str = "less -R";
+
+/// Prints `text`, through the pager if stdout is a terminal and `text` does not fit on it.
+pub fn show(text: &str, enabled: bool) {
+    if !enabled || !stdout().is_terminal() || fits_terminal(text) {
+        print!("{}", text);
+        return;
+    }
+
+    let pager = env::var("PAGER").ok().filter(|p| !p.trim().is_empty()).unwrap_or(DEFAULT_PAGER.to_string());
+    let mut command = Command::new("sh");
+    // PAGER may contain arguments, so let the shell split it
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    // Same defaults as git: quit if one screen, keep colors, do not clear the screen
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+
+    match command.spawn() {
+        Ok(mut child) => {
+            // Quitting the pager early closes the pipe, that is no error
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            let _ = child.wait();
+        },
+        Err(_) => print!("{}", text),
+    }
+}
+
+fn fits_terminal(text: &str) -> bool {
+    match terminal_size::terminal_size() {
+        Some((_, terminal_size::Height(height))) => text.lines().count() < height as usize,
+        None => true,
+    }
+}
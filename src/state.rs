@@ -0,0 +1,36 @@
+// Location of persistent files (cache, snapshots, ...).
+
+use directories::ProjectDirs;
+use std::fs;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+/// Returns the state directory and makes sure it exists and is writable.
+///
+/// Without an explicit directory the platform cache directory is used, e.g. `~/.cache/rgf` on
+/// Linux.
+pub fn resolve(dir: Option<&Path>) -> Result<PathBuf, String> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => match ProjectDirs::from("", "", "rgf") {
+            Some(dirs) => dirs.cache_dir().to_path_buf(),
+            None => return Err("No home directory found, please pass --state-dir".to_string()),
+        },
+    };
+
+    if let Err(e) = fs::create_dir_all(&dir) {
+        return Err(format!("Failed to create state dir {}: {}", dir.display(), e));
+    }
+
+    // There is no portable way to ask for write permission, so just try it.
+    let probe = dir.join(".rgf-write-probe");
+    match fs::write(&probe, b"") {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Ok(dir)
+        },
+        Err(e) => Err(format!("State dir {} is not writable: {}", dir.display(), e)),
+    }
+}
@@ -0,0 +1,107 @@
+// Templates of `--output-template-file`.
+//
+// A template is text with `{{field}}` placeholders and one `{{#forks}}...{{/forks}}` section,
+// which is repeated for every fork. The text before the section is the header, the text after it
+// the footer. Placeholders are checked against the known fields when the template is parsed, so a
+// typo fails before any request is made.
+
+#[derive(Clone, Debug)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Template {
+    header: Vec<Part>,
+    fork: Vec<Part>,
+    footer: Vec<Part>,
+}
+
+const LOOP_START: &str = "#forks";
+const LOOP_END: &str = "/forks";
+
+/// Parses `input`, `fork_fields` may be used inside the fork section and `outer_fields` in the
+/// header and footer.
+pub fn parse(input: &str, fork_fields: &[&str], outer_fields: &[&str]) -> Result<Template, String> {
+    let line_of = |offset: usize| input[..offset].matches('\n').count() + 1;
+
+    let mut sections: Vec<Vec<Part>> = vec![Vec::new()];
+    let mut rest = input;
+    while let Some(start) = rest.find("{{") {
+        let offset = input.len() - rest.len() + start;
+        let Some(len) = rest[start + 2..].find("}}") else {
+            return Err(format!("line {}: {{{{ is not closed with }}}}", line_of(offset)));
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        if start > 0 {
+            sections.last_mut().expect("There is always a section").push(Part::Text(rest[..start].to_string()));
+        }
+        rest = &rest[start + 2 + len + 2..];
+
+        let in_loop = sections.len() == 2;
+        match name {
+            LOOP_START | LOOP_END => {
+                if name == LOOP_START && sections.len() != 1 {
+                    return Err(format!("line {}: only one {{{{#forks}}}} section is allowed", line_of(offset)));
+                }
+                if name == LOOP_END && !in_loop {
+                    return Err(format!("line {}: {{{{/forks}}}} without {{{{#forks}}}}", line_of(offset)));
+                }
+                // The line break after a section marker belongs to it, not to the output
+                rest = rest.strip_prefix('\n').unwrap_or(rest);
+                sections.push(Vec::new());
+            },
+            _ => {
+                let (fields, place) = if in_loop { (fork_fields, "the {{#forks}} section") } else { (outer_fields, "the header or footer") };
+                if !fields.contains(&name) {
+                    return Err(format!("line {}: unknown placeholder {{{{{}}}}} in {}, known there: {}", line_of(offset), name, place, fields.join(", ")));
+                }
+                sections.last_mut().expect("There is always a section").push(Part::Field(name.to_string()));
+            },
+        }
+    }
+    if !rest.is_empty() {
+        sections.last_mut().expect("There is always a section").push(Part::Text(rest.to_string()));
+    }
+
+    match sections.len() {
+        1 => Err("The template has no {{#forks}}...{{/forks}} section".to_string()),
+        2 => Err("{{#forks}} is not closed with {{/forks}}".to_string()),
+        _ => {
+            let mut sections = sections.into_iter();
+            let mut next = || sections.next().unwrap_or_default();
+            Ok(Template { header: next(), fork: next(), footer: next() })
+        },
+    }
+}
+
+fn render_parts(parts: &[Part], value: &dyn Fn(&str) -> String, out: &mut String) {
+    for part in parts {
+        match part {
+            Part::Text(text) => out.push_str(text),
+            Part::Field(name) => out.push_str(&value(name)),
+        }
+    }
+}
+
+impl Template {
+    /// Fields the template uses anywhere.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.header.iter().chain(&self.fork).chain(&self.footer).filter_map(|part| match part {
+            Part::Field(name) => Some(name.as_str()),
+            Part::Text(_) => None,
+        })
+    }
+
+    /// Header, every fork and footer, with the values of `outer` and of every fork.
+    pub fn render<F: Fn(&str) -> String>(&self, outer: &dyn Fn(&str) -> String, forks: impl IntoIterator<Item = F>) -> String {
+        let mut out = String::new();
+        render_parts(&self.header, outer, &mut out);
+        for fork in forks {
+            render_parts(&self.fork, &fork, &mut out);
+        }
+        render_parts(&self.footer, outer, &mut out);
+        out
+    }
+}
@@ -0,0 +1,113 @@
+// Colors of the terminal output, chosen to stay readable on light and dark backgrounds.
+
+use clap::ValueEnum;
+use std::env;
+use std::io::{
+    stdout,
+    IsTerminal,
+};
+use std::time::Duration;
+
+/// How long the terminal may take to answer the background color query.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Theme {
+    /// Detect the terminal background, no colors if stdout is no terminal
+    Auto,
+    /// Palette for light terminal backgrounds
+    Light,
+    /// Palette for dark terminal backgrounds
+    Dark,
+    /// No colors at all
+    None,
+}
+
+/// ANSI SGR codes per kind of output. Empty codes print the text unchanged.
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    pub added: &'static str,
+    pub planned: &'static str,
+    pub existing: &'static str,
+    pub failed: &'static str,
+    pub removed: &'static str,
+    pub header: &'static str,
+    /// Forks sharing a topic with their source, see `--highlight-shared-topics`
+    pub highlight: &'static str,
+}
+
+const DARK: Palette = Palette {
+    added: "1;32",
+    planned: "1;33",
+    existing: "37",
+    failed: "1;31",
+    removed: "31",
+    header: "1;36",
+    highlight: "1;35",
+};
+
+// Yellow and light gray are hard to read on white, use darker colors instead.
+const LIGHT: Palette = Palette {
+    added: "32",
+    planned: "34",
+    existing: "90",
+    failed: "31",
+    removed: "31",
+    header: "35",
+    highlight: "36",
+};
+
+const NONE: Palette = Palette {
+    added: "",
+    planned: "",
+    existing: "",
+    failed: "",
+    removed: "",
+    header: "",
+    highlight: "",
+};
+
+impl Palette {
+    pub fn new(theme: Theme) -> Palette {
+        match theme {
+            Theme::Light => LIGHT,
+            Theme::Dark => DARK,
+            Theme::None => NONE,
+            Theme::Auto => {
+                if env::var_os("NO_COLOR").is_some() || !stdout().is_terminal() {
+                    return NONE;
+                }
+                match detect_light_background() {
+                    Some(true) => LIGHT,
+                    // Detection failed, the default palette is the dark one
+                    Some(false) | None => DARK,
+                }
+            },
+        }
+    }
+
+    /// Wraps `text` in the escape sequences of `color`.
+    pub fn paint(&self, color: &str, text: &str) -> String {
+        if color.is_empty() {
+            return text.to_string();
+        }
+        format!("\x1b[{}m{}\x1b[0m", color, text)
+    }
+}
+
+/// Asks the environment and then the terminal itself for the background brightness.
+fn detect_light_background() -> Option<bool> {
+    // Set by rxvt, konsole and others as "<fg>;<bg>" or "<fg>;<default>;<bg>"
+    if let Ok(colorfgbg) = env::var("COLORFGBG") {
+        if let Some(Ok(bg)) = colorfgbg.rsplit(';').next().map(str::parse::<u8>) {
+            // 7 (light gray) and the bright colors above 8 (dark gray) are light
+            return Some(bg == 7 || bg > 8);
+        }
+    }
+
+    match termbg::theme(QUERY_TIMEOUT) {
+        Ok(termbg::Theme::Light) => Some(true),
+        Ok(termbg::Theme::Dark) => Some(false),
+        Err(_) => None,
+    }
+}